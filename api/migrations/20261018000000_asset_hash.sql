@@ -0,0 +1,2 @@
+-- Content Hashes for Assets
+ALTER TABLE asset ADD COLUMN hash TEXT DEFAULT '' NOT NULL; -- Lowercase hex SHA-256 hash of `data`, used to skip unchanged imports and for client cache revalidation
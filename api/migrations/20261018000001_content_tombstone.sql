@@ -0,0 +1,27 @@
+-- Tombstones for Content Sync, records deleted `content` and `asset` ids so client caches can be patched incrementally
+CREATE TABLE content_tombstone (
+    id                 INTEGER  NOT NULL PRIMARY KEY, -- Snowflake ID of the deleted `content` row
+    deleted_at         INTEGER  DEFAULT (unixepoch()) NOT NULL -- Unix timestamp in seconds
+) STRICT;
+
+CREATE TABLE asset_tombstone (
+    id                 INTEGER  NOT NULL PRIMARY KEY, -- Snowflake ID of the deleted `asset` row
+    deleted_at         INTEGER  DEFAULT (unixepoch()) NOT NULL -- Unix timestamp in seconds
+) STRICT;
+
+CREATE TRIGGER content_after_delete AFTER DELETE ON content BEGIN
+    INSERT INTO content_tombstone (id) VALUES (OLD.id) ON CONFLICT(id) DO UPDATE SET deleted_at=(unixepoch());
+END;
+
+CREATE TRIGGER content_after_insert AFTER INSERT ON content BEGIN
+    DELETE FROM content_tombstone WHERE id = NEW.id;
+END;
+
+-- User generated assets aren't synced, so they don't need tombstones.
+CREATE TRIGGER asset_after_delete AFTER DELETE ON asset WHEN OLD.is_user_generated = FALSE BEGIN
+    INSERT INTO asset_tombstone (id) VALUES (OLD.id) ON CONFLICT(id) DO UPDATE SET deleted_at=(unixepoch());
+END;
+
+CREATE TRIGGER asset_after_insert AFTER INSERT ON asset BEGIN
+    DELETE FROM asset_tombstone WHERE id = NEW.id;
+END;
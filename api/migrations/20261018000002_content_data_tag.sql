@@ -0,0 +1,13 @@
+-- Tag Content Data with its Content Type, only `Item` and `UserGeneratedItem` content has extra data
+UPDATE content SET data = CASE content_type
+    WHEN 100 THEN json_set(data, '$.content_type', 'Item')
+    WHEN 101 THEN json_set(data, '$.content_type', 'UserGeneratedItem')
+    ELSE json_object('content_type', CASE content_type
+        WHEN 1 THEN 'Class'
+        WHEN 2 THEN 'GameFeature'
+        WHEN 200 THEN 'Companion'
+        WHEN 300 THEN 'CharacterOption'
+        WHEN 301 THEN 'UserGeneratedOption'
+        ELSE 'None'
+    END)
+END;
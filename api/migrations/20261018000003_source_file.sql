@@ -0,0 +1,7 @@
+-- Source Files for Imported Rows, NULL for rows that weren't imported from data files
+ALTER TABLE user ADD COLUMN source_file TEXT; -- Path of the data file relative to `data/content` this row was imported from
+ALTER TABLE access_token ADD COLUMN source_file TEXT; -- Path of the data file relative to `data/content` this row was imported from
+ALTER TABLE game_server ADD COLUMN source_file TEXT; -- Path of the data file relative to `data/content` this row was imported from
+ALTER TABLE world ADD COLUMN source_file TEXT; -- Path of the data file relative to `data/content` this row was imported from
+ALTER TABLE asset ADD COLUMN source_file TEXT; -- Path of the data file relative to `data/content` this row was imported from
+ALTER TABLE content ADD COLUMN source_file TEXT; -- Path of the data file relative to `data/content` this row was imported from
@@ -0,0 +1,10 @@
+-- Equipment Slots, defined by data files. Each slot is a column of `gearset` and `outfit`
+CREATE TABLE equipment_slot (
+    id                 INTEGER  NOT NULL PRIMARY KEY CHECK (id BETWEEN 0 AND 15), -- Index of the `gearset.item_id_{id}` and `outfit.item_content_id_{id}` columns
+    updated_at         INTEGER  DEFAULT (unixepoch()) NOT NULL, -- Unix timestamp in seconds
+    name               TEXT     NOT NULL UNIQUE COLLATE NOCASE, -- Case insensitive slot name, should be between 2 and 30 legal characters with at most 4 spaces
+    source_file        TEXT -- Path of the data file relative to `data/content` this row was imported from
+) STRICT;
+
+-- BaseClass, `class.class_content_id` 0 references this row so every character can have a `character_status`
+INSERT INTO content (id, name, content_type, content_subtype, data) VALUES (0, 'BaseClass', 1, 0, '{"content_type":"Class"}') ON CONFLICT(id) DO NOTHING;
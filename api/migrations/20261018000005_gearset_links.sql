@@ -0,0 +1,39 @@
+-- Gearset Links, `gearset.linked_class_id` referenced `outfit` by mistake. SQLite can't change a foreign key in place, so the table is rebuilt.
+-- References to `gearset` are only checked when the migration commits, after its rows have been inserted again.
+PRAGMA defer_foreign_keys = ON;
+
+CREATE TEMP TABLE gearset_copy AS SELECT * FROM gearset;
+DROP TABLE gearset;
+
+CREATE TABLE gearset (
+    id                 INTEGER  NOT NULL PRIMARY KEY, -- Snowflake ID, alias of rowid
+    updated_at         INTEGER  DEFAULT (unixepoch()) NOT NULL, -- Unix timestamp in seconds
+    character_id       INTEGER  NOT NULL REFERENCES character(id),
+    name               TEXT     DEFAULT "BASE" NOT NULL COLLATE NOCASE, -- Case insensitive indexed name, special value BASE means this is the default gearset that is directly modified when equipping gear.
+    statistics         TEXT     DEFAULT "{}" NOT NULL, -- JSON object
+    linked_class_id    INTEGER  REFERENCES class(id), -- NULL if no Class is linked. Activating the gearset makes this class active.
+    linked_outfit_id   INTEGER  REFERENCES outfit(id), -- NULL if no Outfit is linked. Activating the gearset applies this outfit.
+    item_id_0          INTEGER  REFERENCES item(id), -- NULL if slot is empty or not implemented.
+    item_id_1          INTEGER  REFERENCES item(id), -- NULL if slot is empty or not implemented.
+    item_id_2          INTEGER  REFERENCES item(id), -- NULL if slot is empty or not implemented.
+    item_id_3          INTEGER  REFERENCES item(id), -- NULL if slot is empty or not implemented.
+    item_id_4          INTEGER  REFERENCES item(id), -- NULL if slot is empty or not implemented.
+    item_id_5          INTEGER  REFERENCES item(id), -- NULL if slot is empty or not implemented.
+    item_id_6          INTEGER  REFERENCES item(id), -- NULL if slot is empty or not implemented.
+    item_id_7          INTEGER  REFERENCES item(id), -- NULL if slot is empty or not implemented.
+    item_id_8          INTEGER  REFERENCES item(id), -- NULL if slot is empty or not implemented.
+    item_id_9          INTEGER  REFERENCES item(id), -- NULL if slot is empty or not implemented.
+    item_id_10         INTEGER  REFERENCES item(id), -- NULL if slot is empty or not implemented.
+    item_id_11         INTEGER  REFERENCES item(id), -- NULL if slot is empty or not implemented.
+    item_id_12         INTEGER  REFERENCES item(id), -- NULL if slot is empty or not implemented.
+    item_id_13         INTEGER  REFERENCES item(id), -- NULL if slot is empty or not implemented.
+    item_id_14         INTEGER  REFERENCES item(id), -- NULL if slot is empty or not implemented.
+    item_id_15         INTEGER  REFERENCES item(id), -- NULL if slot is empty or not implemented.
+    UNIQUE(character_id, name)
+) STRICT;
+
+INSERT INTO gearset SELECT * FROM gearset_copy;
+DROP TABLE gearset_copy;
+
+-- BASE gearsets link to the BaseClass, so deactivating an overlayed gearset makes the BaseClass active again
+UPDATE gearset SET linked_class_id = (SELECT class.id FROM class WHERE class.character_id = gearset.character_id AND class.class_content_id = 0) WHERE name = 'BASE' AND linked_class_id IS NULL;
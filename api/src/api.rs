@@ -1,3 +1,266 @@
+use crate::{
+    model::{self, fields, tables},
+    queries::{
+        gearset::{GearSlots, GearsetChanges},
+        inventory::ItemChanges,
+        outfit::{AppearanceSlots, OutfitChanges, ResolvedAppearance},
+    },
+    utils::authentication::id_to_base32,
+};
+use tonic::Status;
+
 tonic::include_proto!("api");
 
 pub(crate) const FILE_DESCRIPTOR_SET: &[u8] = tonic::include_file_descriptor_set!("api_descriptor");
+
+impl TryFrom<tables::User> for User {
+    type Error = Status;
+
+    fn try_from(user: tables::User) -> Result<Self, Self::Error> {
+        Ok(User {
+            id: user.id,
+            handle: id_to_base32(user.handle)
+                .ok_or(Status::internal("Failed to encode handle."))?,
+            updated_at: user.updated_at.and_utc().timestamp(),
+            username: user.username,
+            role: user.role as i32,
+        })
+    }
+}
+
+impl From<tables::AccessToken> for AccessToken {
+    fn from(access_token: tables::AccessToken) -> Self {
+        AccessToken {
+            id: access_token.id,
+            access_level: access_token.access_level as i32,
+            game_server_id: access_token.game_server_id,
+            expires_at: access_token.expires_at,
+        }
+    }
+}
+
+impl From<tables::GameServer> for GameServerData {
+    fn from(game_server: tables::GameServer) -> Self {
+        GameServerData {
+            id: game_server.id,
+            created_at: game_server.created_at.and_utc().timestamp(),
+            updated_at: game_server.updated_at.and_utc().timestamp(),
+            region_code: game_server.region_code,
+            display_name: game_server.display_name,
+        }
+    }
+}
+
+impl From<tables::World> for World {
+    fn from(world: tables::World) -> Self {
+        World {
+            id: world.id,
+            created_at: world.created_at.and_utc().timestamp(),
+            updated_at: world.updated_at.and_utc().timestamp(),
+            game_server_id: world.game_server_id,
+            display_name: world.display_name,
+        }
+    }
+}
+
+impl From<tables::GameInfo> for GameInfo {
+    fn from(game_info: tables::GameInfo) -> Self {
+        GameInfo {
+            created_at: game_info.created_at.and_utc().timestamp(),
+            updated_at: game_info.updated_at.and_utc().timestamp(),
+            game_id: game_info.game_id,
+            game_version: game_info.game_version,
+            supported_client_game_ids: game_info.supported_client_game_ids.0,
+            supported_client_game_versions: game_info.supported_client_game_versions.0,
+            game_display_name: game_info.game_display_name,
+        }
+    }
+}
+
+impl From<tables::Content> for Content {
+    fn from(content: tables::Content) -> Self {
+        Content {
+            id: content.id,
+            updated_at: content.updated_at.and_utc().timestamp(),
+            name: content.name,
+            content_type: content.content_type as i32,
+            content_subtype: content.content_subtype as i32,
+            data: match content.data.0 {
+                fields::ContentData::Item(data) | fields::ContentData::UserGeneratedItem(data) => {
+                    Some(content::Data::Item(ItemContentData {
+                        stack_size: data.stack_size,
+                        is_unique: data.is_unique,
+                        is_soulbound: data.is_soulbound,
+                        tradability: data.tradability as i32,
+                        capacity: data.capacity,
+                        slot: data.slot,
+                    }))
+                }
+                _ => None,
+            },
+            asset_id_0: content.asset_id_0,
+            asset_id_1: content.asset_id_1,
+            asset_id_2: content.asset_id_2,
+            asset_id_3: content.asset_id_3,
+            asset_id_4: content.asset_id_4,
+            is_user_generated: content.is_user_generated,
+            base_content_id: content.base_content_id,
+        }
+    }
+}
+
+impl From<tables::Asset> for AssetFile {
+    fn from(asset: tables::Asset) -> Self {
+        AssetFile {
+            id: asset.id,
+            updated_at: asset.updated_at.and_utc().timestamp(),
+            path: asset.path,
+            file_type: asset.file_type,
+            size: asset.size,
+            is_user_generated: asset.is_user_generated,
+            data: Some(match asset.data {
+                fields::AssetData::Blob(blob) => asset_file::Data::Blob(blob),
+                fields::AssetData::String(string) => asset_file::Data::String(string),
+            }),
+            hash: asset.hash,
+        }
+    }
+}
+
+impl From<model::ContentChange> for ContentChange {
+    fn from(change: model::ContentChange) -> Self {
+        ContentChange {
+            changed_at: change.changed_at,
+            game_version: change.game_version,
+            changed_files: change.changed_files,
+        }
+    }
+}
+
+impl From<tables::Item> for ItemInstance {
+    fn from(item: tables::Item) -> Self {
+        ItemInstance {
+            id: item.id,
+            character_id: item.character_id,
+            item_content_id: item.item_content_id,
+            quantity: item.quantity,
+            location: item.location as i32,
+            quality: item.quality as i32,
+            container_item_id: item.container_item_id,
+            extra_character_id_0: item.extra_character_id_0,
+            extra_character_id_1: item.extra_character_id_1,
+            extra_content_id: item.extra_content_id,
+        }
+    }
+}
+
+impl From<tables::ItemCollectionEntry> for ItemCollectionEntry {
+    fn from(entry: tables::ItemCollectionEntry) -> Self {
+        ItemCollectionEntry {
+            id: entry.id,
+            character_id: entry.character_id,
+            item_content_id: entry.item_content_id,
+            location: entry.location as i32,
+            quality: entry.quality as i32,
+        }
+    }
+}
+
+/// Turns gear slots into a list of the slots that hold an item.
+pub fn gear_slot_list(slots: GearSlots) -> Vec<GearSlot> {
+    slots
+        .into_iter()
+        .enumerate()
+        .filter_map(|(slot, item_id)| {
+            item_id.map(|item_id| GearSlot {
+                slot: slot as i64,
+                item_id,
+            })
+        })
+        .collect()
+}
+
+impl From<tables::Gearset> for Gearset {
+    fn from(gearset: tables::Gearset) -> Self {
+        Gearset {
+            id: gearset.id,
+            updated_at: gearset.updated_at.and_utc().timestamp(),
+            character_id: gearset.character_id,
+            name: gearset.name,
+            linked_class_id: gearset.linked_class_id,
+            linked_outfit_id: gearset.linked_outfit_id,
+            slot_list: gear_slot_list(gearset.item_id),
+        }
+    }
+}
+
+impl From<GearsetChanges> for GearsetReply {
+    fn from(changes: GearsetChanges) -> Self {
+        GearsetReply {
+            gearset: Some(changes.gearset.into()),
+            equipped_slot_list: gear_slot_list(changes.equipped),
+            item_list: changes.items.into_iter().map(|i| i.into()).collect(),
+            active_gearset_id: changes.status.active_gearset_id,
+            active_class_id: changes.status.active_class_id,
+            active_outfit_id: changes.status.active_outfit_id,
+        }
+    }
+}
+
+/// Turns appearance slots into a list of the slots that show content.
+pub fn appearance_slot_list(slots: AppearanceSlots) -> Vec<AppearanceSlot> {
+    slots
+        .into_iter()
+        .enumerate()
+        .filter_map(|(slot, content_id)| {
+            content_id.map(|content_id| AppearanceSlot {
+                slot: slot as i64,
+                content_id,
+            })
+        })
+        .collect()
+}
+
+impl From<tables::Outfit> for Outfit {
+    fn from(outfit: tables::Outfit) -> Self {
+        Outfit {
+            id: outfit.id,
+            updated_at: outfit.updated_at.and_utc().timestamp(),
+            character_id: outfit.character_id,
+            name: outfit.name,
+            customization: outfit
+                .customization
+                .and_then(|c| serde_json::to_string(&c).ok()),
+            slot_list: appearance_slot_list(outfit.item_content_id),
+        }
+    }
+}
+
+impl From<ResolvedAppearance> for Appearance {
+    fn from(appearance: ResolvedAppearance) -> Self {
+        Appearance {
+            slot_list: appearance_slot_list(appearance.content_id),
+            customization: appearance
+                .customization
+                .and_then(|c| serde_json::to_string(&c).ok()),
+        }
+    }
+}
+
+impl From<OutfitChanges> for OutfitReply {
+    fn from(changes: OutfitChanges) -> Self {
+        OutfitReply {
+            outfit: Some(changes.outfit.into()),
+            appearance: Some(changes.appearance.into()),
+        }
+    }
+}
+
+impl From<ItemChanges> for ItemChangeReply {
+    fn from(changes: ItemChanges) -> Self {
+        ItemChangeReply {
+            item_list: changes.items.into_iter().map(|i| i.into()).collect(),
+            deleted_item_id_list: changes.deleted_item_ids,
+        }
+    }
+}
@@ -0,0 +1,210 @@
+use crate::{
+    model::Ref, queries::asset::get_asset_header_query, services::asset::spawn_asset_data_reader,
+};
+use axum::{
+    Router,
+    body::Body,
+    extract::{Path, State},
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use sqlx::{Pool, Sqlite};
+use tokio_stream::StreamExt;
+use tower_http::{
+    services::{ServeDir, ServeFile},
+    set_header::SetResponseHeaderLayer,
+};
+
+/// Builds the HTTP router that serves assets from the database, the `client_data.sqlite` snapshot and the web client bundle.
+pub fn router(db: Pool<Sqlite>, client_data_path: &str, web_client_dir: &str) -> Router {
+    Router::new()
+        .route("/asset/*path", get(get_asset))
+        .with_state(db)
+        .route_service("/client_data.sqlite", ServeFile::new(client_data_path))
+        .fallback_service(ServeDir::new(web_client_dir))
+        // The web client needs cross origin isolation for multithreading.
+        .layer(SetResponseHeaderLayer::if_not_present(
+            header::HeaderName::from_static("cross-origin-opener-policy"),
+            HeaderValue::from_static("same-origin"),
+        ))
+        .layer(SetResponseHeaderLayer::if_not_present(
+            header::HeaderName::from_static("cross-origin-embedder-policy"),
+            HeaderValue::from_static("require-corp"),
+        ))
+}
+
+/// Serves `Asset.data` by virtual path using `Asset.hash` as the ETag, with support for single byte range requests.
+/// Assets with an empty hash have no ETag and are always sent in full.
+async fn get_asset(
+    State(db): State<Pool<Sqlite>>,
+    Path(path): Path<String>,
+    headers: HeaderMap,
+) -> Response {
+    let asset = match get_asset_header_query(&db, Ref::Name(format!("/{}", path))).await {
+        Ok(asset) => asset,
+        Err(_) => return StatusCode::NOT_FOUND.into_response(),
+    };
+    let etag = (!asset.hash.is_empty()).then(|| format!("\"{}\"", asset.hash));
+    if let Some(etag) = &etag {
+        let is_cached = headers
+            .get(header::IF_NONE_MATCH)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| {
+                value
+                    .split(',')
+                    .map(|tag| tag.trim().trim_start_matches("W/"))
+                    .any(|tag| tag == "*" || tag == etag)
+            });
+        if is_cached {
+            return (StatusCode::NOT_MODIFIED, [(header::ETAG, etag.to_owned())]).into_response();
+        }
+    }
+
+    let range = match headers
+        .get(header::RANGE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| parse_range(value, asset.size))
+    {
+        Some(ByteRange::Satisfiable(start, end)) => Some((start, end)),
+        Some(ByteRange::Unsatisfiable) => {
+            return (
+                StatusCode::RANGE_NOT_SATISFIABLE,
+                [(header::CONTENT_RANGE, format!("bytes */{}", asset.size))],
+            )
+                .into_response();
+        }
+        None => None,
+    };
+    let (status, start, end) = match range {
+        Some((start, end)) => (StatusCode::PARTIAL_CONTENT, start, end),
+        None => (StatusCode::OK, 0, asset.size),
+    };
+
+    let chunks = spawn_asset_data_reader(db, asset.id, start, end)
+        .map(|chunk| chunk.map(|(_, data)| data).map_err(std::io::Error::other));
+    let mut response = (
+        status,
+        [
+            (header::CONTENT_TYPE, asset.file_type),
+            (header::CONTENT_LENGTH, (end - start).to_string()),
+            (header::ACCEPT_RANGES, "bytes".to_owned()),
+            (header::CACHE_CONTROL, "no-cache".to_owned()),
+        ],
+        Body::from_stream(chunks),
+    )
+        .into_response();
+    if let Some(etag) = etag.and_then(|etag| HeaderValue::from_str(&etag).ok()) {
+        response.headers_mut().insert(header::ETAG, etag);
+    }
+    if range.is_some()
+        && let Ok(content_range) =
+            HeaderValue::from_str(&format!("bytes {}-{}/{}", start, end - 1, asset.size))
+    {
+        response
+            .headers_mut()
+            .insert(header::CONTENT_RANGE, content_range);
+    }
+    response
+}
+
+/// A single byte range requested by a `Range` header.
+#[derive(Debug, PartialEq)]
+enum ByteRange {
+    Satisfiable(i64, i64), // `start..end`, clamped to the asset size
+    Unsatisfiable,
+}
+
+/// Parses a `Range` header holding a single byte range. Returns None for headers that aren't supported, like multiple ranges, or aren't valid, so the whole asset is sent instead.
+fn parse_range(value: &str, size: i64) -> Option<ByteRange> {
+    let spec = value.strip_prefix("bytes=")?.trim();
+    if spec.contains(',') {
+        return None;
+    }
+    let (first, last) = spec.split_once('-')?;
+    let (start, end) = match (first.trim(), last.trim()) {
+        ("", suffix) => {
+            let suffix = parse_position(suffix)?;
+            ((size - suffix).max(0), size)
+        }
+        (first, "") => (parse_position(first)?, size),
+        (first, last) => {
+            let (first, last) = (parse_position(first)?, parse_position(last)?);
+            if last < first {
+                return None;
+            }
+            (first, (last + 1).min(size))
+        }
+    };
+    if start >= end {
+        return Some(ByteRange::Unsatisfiable);
+    }
+    Some(ByteRange::Satisfiable(start, end))
+}
+
+/// Parses a byte position, which is only digits so signs aren't accepted.
+fn parse_position(value: &str) -> Option<i64> {
+    if !value.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    value.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn suffix_range_counts_from_the_end() {
+        assert_eq!(
+            parse_range("bytes=-10", 100),
+            Some(ByteRange::Satisfiable(90, 100))
+        );
+        assert_eq!(
+            parse_range("bytes=-200", 100),
+            Some(ByteRange::Satisfiable(0, 100))
+        );
+        assert_eq!(parse_range("bytes=-0", 100), Some(ByteRange::Unsatisfiable));
+    }
+
+    #[test]
+    fn open_ended_range_runs_to_the_end() {
+        assert_eq!(
+            parse_range("bytes=40-", 100),
+            Some(ByteRange::Satisfiable(40, 100))
+        );
+    }
+
+    #[test]
+    fn end_is_clamped_to_the_size() {
+        assert_eq!(
+            parse_range("bytes=10-19", 100),
+            Some(ByteRange::Satisfiable(10, 20))
+        );
+        assert_eq!(
+            parse_range("bytes=90-500", 100),
+            Some(ByteRange::Satisfiable(90, 100))
+        );
+    }
+
+    #[test]
+    fn range_past_the_end_is_unsatisfiable() {
+        assert_eq!(
+            parse_range("bytes=100-", 100),
+            Some(ByteRange::Unsatisfiable)
+        );
+        assert_eq!(
+            parse_range("bytes=150-200", 100),
+            Some(ByteRange::Unsatisfiable)
+        );
+    }
+
+    #[test]
+    fn unsupported_ranges_are_ignored() {
+        assert_eq!(parse_range("bytes=0-1,5-6", 100), None);
+        assert_eq!(parse_range("bytes=5-1", 100), None);
+        assert_eq!(parse_range("bytes=a-b", 100), None);
+        assert_eq!(parse_range("bytes=--5", 100), None);
+        assert_eq!(parse_range("items=0-1", 100), None);
+    }
+}
@@ -1,17 +1,35 @@
 #![allow(dead_code)]
+#![allow(clippy::result_large_err)]
 mod api;
+mod http;
 mod model;
 mod queries;
 mod services;
 mod utils;
-use api::{game_data_server::GameDataServer, users_server::UsersServer};
-use queries::game_info::get_game_info_query;
+mod watch;
+use api::{
+    administration_server::AdministrationServer, asset_server::AssetServer,
+    game_data_server::GameDataServer, users_server::UsersServer,
+};
+use queries::{authentication::delete_expired_credentials_query, game_info::get_game_info_query};
+use services::administration::AdministrationService;
+use services::asset::AssetService;
 use services::game_data::GameDataService;
 use services::users::UsersService;
 use sqlx::SqlitePool;
-use std::{env, net::SocketAddr};
+use std::{
+    env,
+    future::IntoFuture,
+    net::SocketAddr,
+    time::Duration,
+};
+use tokio::sync::broadcast;
 use tonic::{transport::Server, Request, Status};
 use utils::{new_sonyflake, parse_range};
+use watch::watch_content;
+
+/// How often expired `user_session` and `access_token` rows are deleted.
+const CREDENTIAL_REAPER_INTERVAL: Duration = Duration::from_secs(60 * 15);
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -24,6 +42,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         env::var("MACHINE_ID_RANGE").expect("Environment variable 'MACHINE_ID_RANGE' not found");
     let mut machine_ids =
         parse_range(machine_id_range).expect("'MACHINE_ID_RANGE' must be a pair of integers");
+    let http_address =
+        env::var("HTTP_ADDRESS").expect("Environment variable 'HTTP_ADDRESS' not found");
+    let asset_base_url =
+        env::var("ASSET_BASE_URL").unwrap_or_else(|_| format!("http://{}/asset", http_address));
+    let client_data_path =
+        env::var("CLIENT_DATA_PATH").unwrap_or_else(|_| "client_data.sqlite".to_owned());
+    let web_client_dir = env::var("WEB_CLIENT_DIR").unwrap_or_else(|_| "../out/client".to_owned());
 
     let db0 = SqlitePool::connect(&database_url)
         .await
@@ -31,6 +56,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let db1 = SqlitePool::connect(&database_url)
         .await
         .expect("Could not load SQLite database");
+    let db2 = SqlitePool::connect(&database_url)
+        .await
+        .expect("Could not load SQLite database");
+    let db3 = SqlitePool::connect(&database_url)
+        .await
+        .expect("Could not load SQLite database");
 
     let game_info = get_game_info_query(&db0)
         .await
@@ -40,28 +71,75 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         game_info.game_id, game_info.game_version
     );
 
+    tokio::spawn(reap_expired_credentials(db1.clone()));
+    // Watch mode reloads the data files into this database, so it's only enabled for dev game versions.
+    let content_changes = game_info
+        .game_version
+        .starts_with("dev")
+        .then(|| broadcast::channel(16).0);
+    if let Some(changes) = &content_changes {
+        tokio::spawn(watch_content(db3.clone(), changes.clone()));
+    }
+
     let game_data_service = GameDataService::new(db0, new_sonyflake(&mut machine_ids).unwrap());
     let users_service = UsersService::new(db1, new_sonyflake(&mut machine_ids).unwrap());
+    let administration_service =
+        AdministrationService::new(db2, new_sonyflake(&mut machine_ids).unwrap());
+    let asset_service = AssetService::new(
+        db3.clone(),
+        new_sonyflake(&mut machine_ids).unwrap(),
+        asset_base_url,
+        content_changes.clone(),
+    );
+    let http_router = http::router(db3, &client_data_path, &web_client_dir);
     let reflection_service = tonic_reflection::server::Builder::configure()
         .register_encoded_file_descriptor_set(api::FILE_DESCRIPTOR_SET)
         .build_v1()
         .unwrap();
 
     let addr: SocketAddr = address.parse().expect("Unable to parse socket address");
+    let http_addr: SocketAddr = http_address
+        .parse()
+        .expect("Unable to parse HTTP socket address");
+    let http_listener = tokio::net::TcpListener::bind(http_addr).await?;
     println!(
-        "  ☄️ Starting Project Comet Game Data API Service on {}\n",
-        addr
+        "  ☄️ Starting Project Comet Game Data API Service on {} and HTTP asset host on {}\n",
+        addr, http_addr
     );
-    Server::builder()
+    let grpc_server = Server::builder()
         .add_service(reflection_service)
         .add_service(GameDataServer::with_interceptor(game_data_service, echo))
         .add_service(UsersServer::with_interceptor(users_service, echo))
-        .serve(addr)
-        .await?;
+        .add_service(AdministrationServer::with_interceptor(
+            administration_service,
+            echo,
+        ))
+        .add_service(AssetServer::with_interceptor(asset_service, echo))
+        .serve(addr);
+    let http_server = axum::serve(http_listener, http_router).into_future();
+    tokio::select! {
+        result = grpc_server => result?,
+        result = http_server => result?,
+    }
 
     Ok(())
 }
 
+async fn reap_expired_credentials(db: SqlitePool) {
+    let mut interval = tokio::time::interval(CREDENTIAL_REAPER_INTERVAL);
+    loop {
+        interval.tick().await;
+        match delete_expired_credentials_query(&db).await {
+            Ok((0, 0)) => {}
+            Ok((sessions, access_tokens)) => println!(
+                "  Deleted {} expired sessions and {} expired access tokens.\n",
+                sessions, access_tokens
+            ),
+            Err(e) => eprintln!("  Failed to delete expired credentials: {}\n", e),
+        }
+    }
+}
+
 fn echo(req: Request<()>) -> Result<Request<()>, Status> {
     println!("Request: {:?}\n", req);
     Ok(req)
@@ -1,9 +1,13 @@
-use super::tables::{AccessToken, User, UserRecoveryCode, UserSession};
+use super::{
+    fields::AccessLevel,
+    tables::{AccessToken, User, UserRecoveryCode, UserSession},
+};
 use tonic::Status;
 
 #[derive(Debug)]
 pub enum AuthStatus {
     Authenticated(AuthType),
+    Expired,
     Unauthenticated,
 }
 
@@ -14,10 +18,27 @@ pub enum AuthType {
     AccessToken(AccessToken),
 }
 
+#[derive(Debug)]
+pub enum AuthError {
+    Invalid,
+    Expired,
+}
+
+impl From<AuthError> for AuthStatus {
+    fn from(error: AuthError) -> Self {
+        match error {
+            AuthError::Invalid => AuthStatus::Unauthenticated,
+            AuthError::Expired => AuthStatus::Expired,
+        }
+    }
+}
+
 impl AuthStatus {
+    /// Expired credentials are rejected with `failed_precondition`, like `VerifyAccessToken` does, so clients can tell them apart from invalid ones.
     pub fn auth_or(self) -> Result<AuthType, Status> {
         match self {
             AuthStatus::Authenticated(auth_type) => Ok(auth_type),
+            AuthStatus::Expired => Err(Status::failed_precondition("Credentials expired.")),
             AuthStatus::Unauthenticated => Err(Status::unauthenticated("Not authorized.")),
         }
     }
@@ -25,34 +46,35 @@ impl AuthStatus {
     pub fn auth_session_or(self) -> Result<(User, UserSession), Status> {
         match self.auth_or()? {
             AuthType::UserSession(user, user_session) => Ok((user, user_session)),
-            AuthType::UserRecoveryCode(_, _) => {
-                return Err(Status::unauthenticated("Not a session."))
-            }
-            AuthType::AccessToken(_) => return Err(Status::unauthenticated("Not a session.")),
+            AuthType::UserRecoveryCode(_, _) => Err(Status::unauthenticated("Not a session.")),
+            AuthType::AccessToken(_) => Err(Status::unauthenticated("Not a session.")),
         }
     }
 
     pub fn auth_recovery_code_or(self) -> Result<(User, UserRecoveryCode), Status> {
         match self.auth_or()? {
-            AuthType::UserSession(_, _) => {
-                return Err(Status::unauthenticated("Not a recovery code."))
-            }
+            AuthType::UserSession(_, _) => Err(Status::unauthenticated("Not a recovery code.")),
             AuthType::UserRecoveryCode(user, recovery_code) => Ok((user, recovery_code)),
-            AuthType::AccessToken(_) => {
-                return Err(Status::unauthenticated("Not a recovery code."))
-            }
+            AuthType::AccessToken(_) => Err(Status::unauthenticated("Not a recovery code.")),
         }
     }
 
     pub fn auth_access_token_or(self) -> Result<AccessToken, Status> {
         match self.auth_or()? {
-            AuthType::UserSession(_, _) => {
-                return Err(Status::unauthenticated("Not an access_token."))
-            }
+            AuthType::UserSession(_, _) => Err(Status::unauthenticated("Not an access_token.")),
             AuthType::UserRecoveryCode(_, _) => {
-                return Err(Status::unauthenticated("Not an access_token."))
+                Err(Status::unauthenticated("Not an access_token."))
             }
             AuthType::AccessToken(access_token) => Ok(access_token),
         }
     }
+
+    /// Requires an access token with at least `access_level`.
+    pub fn auth_access_level_or(self, access_level: AccessLevel) -> Result<AccessToken, Status> {
+        let access_token = self.auth_access_token_or()?;
+        if access_token.access_level < access_level {
+            return Err(Status::permission_denied("Insufficient access level."));
+        }
+        Ok(access_token)
+    }
 }
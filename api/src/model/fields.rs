@@ -38,12 +38,12 @@ pub enum CharacterGender {
     Advanced = 5, // custom pronouns
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Customization {
     pub gender_details: GenderDetails,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GenderDetails {}
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -88,7 +88,7 @@ pub enum GuildRole {
     Trustee = 1,
 }
 
-#[derive(Debug, Eq, PartialEq, PartialOrd, IntoPrimitive, TryFromPrimitive, Type)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, PartialOrd, IntoPrimitive, TryFromPrimitive, Type)]
 #[repr(u16)]
 pub enum ItemLocation {
     Other = 0,
@@ -102,7 +102,9 @@ pub enum ItemLocation {
     Box = 8,
 }
 
-#[derive(Debug, Eq, PartialEq, PartialOrd, IntoPrimitive, TryFromPrimitive, Type)]
+#[derive(
+    Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, IntoPrimitive, TryFromPrimitive, Type,
+)]
 #[repr(u16)]
 pub enum ItemQuality {
     Normal = 0,
@@ -113,7 +115,10 @@ pub enum ItemQuality {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ItemInstanceData {}
 
-#[derive(Debug, Eq, PartialEq, PartialOrd, IntoPrimitive, TryFromPrimitive, Type)]
+/// Ordered by priority, an entry tracks the highest priority location a character holds the item in.
+#[derive(
+    Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, IntoPrimitive, TryFromPrimitive, Type,
+)]
 #[repr(u16)]
 pub enum ItemCollectionEntryLocation {
     NotTracked = 0,
@@ -132,7 +137,9 @@ pub enum AssetData {
     String(String),
 }
 
-#[derive(Debug, Eq, PartialEq, PartialOrd, IntoPrimitive, TryFromPrimitive, Type)]
+#[derive(
+    Debug, Eq, PartialEq, PartialOrd, IntoPrimitive, TryFromPrimitive, Type, Serialize, Deserialize,
+)]
 #[repr(u16)]
 pub enum ContentType {
     None = 0,
@@ -171,32 +178,37 @@ pub enum ContentSubtype {
     Underclothes = 304,
 }
 
-// TODO implement enum support with serde deserialization and toml importing
+/// Extra data for a `Content` row, tagged with the row's `ContentType` when serialized so data for one type can't be read as another.
 #[derive(Debug, Serialize, Deserialize)]
-pub struct ContentData {
+#[serde(tag = "content_type", deny_unknown_fields)]
+pub enum ContentData {
+    None {},
+    Class {},
+    GameFeature {},
+    Item(ItemContentData),
+    UserGeneratedItem(ItemContentData),
+    Companion {},
+    CharacterOption {},
+    UserGeneratedOption {},
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ItemContentData {
     pub stack_size: i64,
     pub is_unique: bool,
     pub is_soulbound: bool,
-    // TODO fix enum serde serialization
-    // pub tradability: ItemTradability,
-    pub tradability: u16,
-}
-
-// pub enum ContentData {
-//     Class {},
-//     Item {
-//         stack_size: i64,
-//         is_unique: bool,
-//         is_soulbound: bool,
-//         tradability: ItemTradability,
-//     },
-//     Companion {},
-//     Unlock {},
-// }
+    pub tradability: ItemTradability,
+    /// Number of item instances an `InventoryContainer` item holds, None for other items.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capacity: Option<i64>,
+    /// `EquipmentSlot` an `Equipment` item is equipped in, None for other items.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slot: Option<i64>,
+}
 
-#[derive(
-    Debug, Eq, PartialEq, PartialOrd, IntoPrimitive, TryFromPrimitive, Serialize, Deserialize,
-)]
+/// Serialized as its number and deserialized from either its name or its number.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, PartialOrd, IntoPrimitive, TryFromPrimitive)]
 #[repr(u16)]
 pub enum ItemTradability {
     Untradeable = 0,
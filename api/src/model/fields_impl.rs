@@ -1,13 +1,18 @@
-use super::fields::AssetData;
+use super::fields::{AssetData, ContentData, ContentType, ItemTradability};
+use serde::{
+    de::{self, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
 use sqlx::{
     encode::IsNull, sqlite::SqliteValueRef, Decode, Encode, Sqlite, Type, TypeInfo, ValueRef,
 };
 use std::{error::Error, fmt};
+use toml::{Table, Value};
 
 impl<'r> Decode<'r, Sqlite> for AssetData {
     fn decode(
         value: SqliteValueRef<'r>,
-    ) -> Result<AssetData, Box<(dyn Error + Send + Sync + 'static)>> {
+    ) -> Result<AssetData, Box<dyn Error + Send + Sync + 'static>> {
         match value.type_info().name() {
             "BLOB" => Ok(AssetData::Blob(
                 (<Vec<u8> as Decode<Sqlite>>::decode(value))?,
@@ -69,3 +74,148 @@ impl TypeInfo for AssetData {
         }
     }
 }
+
+impl ContentData {
+    pub fn content_type(&self) -> ContentType {
+        match self {
+            ContentData::None {} => ContentType::None,
+            ContentData::Class {} => ContentType::Class,
+            ContentData::GameFeature {} => ContentType::GameFeature,
+            ContentData::Item(_) => ContentType::Item,
+            ContentData::UserGeneratedItem(_) => ContentType::UserGeneratedItem,
+            ContentData::Companion {} => ContentType::Companion,
+            ContentData::CharacterOption {} => ContentType::CharacterOption,
+            ContentData::UserGeneratedOption {} => ContentType::UserGeneratedOption,
+        }
+    }
+
+    /// Parses a content row's untagged TOML `data` as the variant for the row's `content_type`.
+    /// Data with missing fields or fields that belong to another type is rejected.
+    pub fn from_toml(
+        content_type: &ContentType,
+        data: Option<&Table>,
+    ) -> Result<ContentData, String> {
+        let mut data = data.cloned().unwrap_or_default();
+        if data.contains_key("content_type") {
+            return Err(
+                "Content data can't set content_type, it comes from the content row.".to_owned(),
+            );
+        }
+        let tag = Value::try_from(content_type).map_err(|e| e.to_string())?;
+        data.insert("content_type".to_owned(), tag);
+        Value::Table(data).try_into().map_err(|e: toml::de::Error| {
+            format!(
+                "Content data doesn't match content_type {:?}. {}",
+                content_type,
+                e.message()
+            )
+        })
+    }
+}
+
+impl Serialize for ItemTradability {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u16(*self as u16)
+    }
+}
+
+impl<'de> Deserialize<'de> for ItemTradability {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ItemTradabilityVisitor)
+    }
+}
+
+struct ItemTradabilityVisitor;
+
+impl Visitor<'_> for ItemTradabilityVisitor {
+    type Value = ItemTradability;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an ItemTradability name or number")
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<ItemTradability, E> {
+        u16::try_from(value)
+            .ok()
+            .and_then(|value| ItemTradability::try_from(value).ok())
+            .ok_or_else(|| E::invalid_value(de::Unexpected::Unsigned(value), &self))
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<ItemTradability, E> {
+        u16::try_from(value)
+            .ok()
+            .and_then(|value| ItemTradability::try_from(value).ok())
+            .ok_or_else(|| E::invalid_value(de::Unexpected::Signed(value), &self))
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<ItemTradability, E> {
+        match value {
+            "Untradeable" => Ok(ItemTradability::Untradeable),
+            "Droppable" => Ok(ItemTradability::Droppable),
+            "NpcTradable" => Ok(ItemTradability::NpcTradable),
+            "PlayerTradable" => Ok(ItemTradability::PlayerTradable),
+            "PlayerMarketable" => Ok(ItemTradability::PlayerMarketable),
+            _ => Err(E::invalid_value(de::Unexpected::Str(value), &self)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(toml: &str) -> Table {
+        toml.parse().unwrap()
+    }
+
+    #[test]
+    fn tradability_deserializes_from_name_or_number() {
+        let by_name: ItemTradability = Value::String("NpcTradable".to_owned()).try_into().unwrap();
+        assert_eq!(by_name, ItemTradability::NpcTradable);
+        let by_number: ItemTradability = Value::Integer(4).try_into().unwrap();
+        assert_eq!(by_number, ItemTradability::PlayerMarketable);
+    }
+
+    #[test]
+    fn unknown_tradability_is_rejected() {
+        assert!(
+            Value::String("Sellable".to_owned())
+                .try_into::<ItemTradability>()
+                .is_err()
+        );
+        assert!(Value::Integer(5).try_into::<ItemTradability>().is_err());
+    }
+
+    #[test]
+    fn item_data_is_read_for_item_rows() {
+        let data = table(
+            "stack_size = 20\nis_unique = false\nis_soulbound = false\ntradability = \"PlayerTradable\"",
+        );
+        match ContentData::from_toml(&ContentType::Item, Some(&data)) {
+            Ok(ContentData::Item(item)) => {
+                assert_eq!(item.stack_size, 20);
+                assert_eq!(item.tradability, ItemTradability::PlayerTradable);
+            }
+            other => panic!("Expected item data, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn item_row_missing_stack_size_is_rejected() {
+        let data = table("is_unique = false\nis_soulbound = false\ntradability = 0");
+        assert!(ContentData::from_toml(&ContentType::Item, Some(&data)).is_err());
+    }
+
+    #[test]
+    fn class_row_with_item_fields_is_rejected() {
+        let data = table("stack_size = 1");
+        assert!(ContentData::from_toml(&ContentType::Class, Some(&data)).is_err());
+        assert!(ContentData::from_toml(&ContentType::Class, None).is_ok());
+    }
+
+    #[test]
+    fn content_type_in_data_is_rejected() {
+        let data = table("content_type = \"Class\"");
+        assert!(ContentData::from_toml(&ContentType::Class, Some(&data)).is_err());
+    }
+}
@@ -2,7 +2,9 @@ pub mod authentication;
 pub mod fields;
 pub mod fields_impl;
 pub mod tables;
+pub mod tables_impl;
 use sqlx::FromRow;
+use tonic::Status;
 
 #[derive(Debug)]
 pub enum Ref {
@@ -14,3 +16,58 @@ pub enum Ref {
 pub struct IdWrapper {
     pub id: i64,
 }
+
+/// `Asset` without its data, used when only the metadata is needed.
+#[derive(Debug, FromRow)]
+pub struct AssetHeader {
+    pub id: i64,
+    pub path: String,
+    pub file_type: String,
+    pub size: i64,
+    pub hash: String,
+}
+
+/// Why `upload_asset_query` failed, kept apart so each cause gets its own status code.
+#[derive(Debug)]
+pub enum UploadError {
+    QuotaExceeded { used: i64, quota: i64 },
+    PathInUse,
+    Internal(String),
+}
+
+/// Why a character's inventory, gearset or outfit query failed, kept apart so each cause gets its own status code.
+#[derive(Debug)]
+pub enum GameDataError {
+    NotFound(String),
+    FailedPrecondition(String),
+    InvalidArgument(String),
+    Internal(String),
+}
+
+impl From<GameDataError> for Status {
+    fn from(error: GameDataError) -> Self {
+        match error {
+            GameDataError::NotFound(message) => Status::not_found(message),
+            GameDataError::FailedPrecondition(message) => Status::failed_precondition(message),
+            GameDataError::InvalidArgument(message) => Status::invalid_argument(message),
+            GameDataError::Internal(message) => Status::internal(message),
+        }
+    }
+}
+
+/// `Content` and non user generated `Asset` rows changed since a client's last sync, along with the ids deleted since then.
+#[derive(Debug)]
+pub struct ContentSync {
+    pub contents: Vec<tables::Content>,
+    pub assets: Vec<tables::Asset>,
+    pub deleted_content_ids: Vec<i64>,
+    pub deleted_asset_ids: Vec<i64>,
+}
+
+/// Sent to `WatchContentChanges` streams after changed data files were imported in watch mode.
+#[derive(Debug, Clone)]
+pub struct ContentChange {
+    pub changed_at: i64,
+    pub game_version: String,
+    pub changed_files: Vec<String>,
+}
@@ -27,6 +27,7 @@ pub struct User {
     pub updated_at: NaiveDateTime, // Unix timestamp in seconds
     pub username: String, // Should not be exposed to other clients. // Unique no case
     pub role: Role,
+    pub source_file: Option<String>, // Data file this row was imported from, None when created through the API
 }
 
 #[derive(Debug, FromRow)]
@@ -57,8 +58,9 @@ pub struct AccessToken {
     pub id: i64,                   // Snowflake ID, alias of rowid
     pub access_token_hash: String, // Hash of the generated access token. Token format is: `default|server:gameserverid|admin_IdBase32Representation_secret`
     pub access_level: AccessLevel,
-    pub game_server_id: String,  // String ID, referances a 'GameServer'
-    pub expires_at: Option<i64>, // Unix timestamp in seconds a certain time in the future
+    pub game_server_id: Option<String>, // String ID, referances a 'GameServer', None when `access_level` is not `GameServer`
+    pub expires_at: Option<i64>,        // Unix timestamp in seconds a certain time in the future
+    pub source_file: Option<String>, // Data file this row was imported from, None when created through the API
 }
 
 #[derive(Debug, FromRow)]
@@ -68,6 +70,7 @@ pub struct GameServer {
     pub updated_at: NaiveDateTime, // Unix timestamp in seconds
     pub region_code: String, // Server location represented by a timezone, using case sensitive tz database identifiers. Ex: 'US/Eastern'
     pub display_name: String, // Server name for end user display
+    pub source_file: Option<String>, // Data file this row was imported from, None when created through the API
 }
 
 #[derive(Debug, FromRow)]
@@ -77,6 +80,7 @@ pub struct World {
     pub updated_at: NaiveDateTime, // Unix timestamp in seconds
     pub game_server_id: String, // String ID, referances a 'GameServer'
     pub display_name: String, // Server name for end user display
+    pub source_file: Option<String>, // Data file this row was imported from, None when created through the API
 }
 /* End Administration Service Schema */
 
@@ -135,7 +139,8 @@ pub struct Class {
     pub class_item_id: Option<i64>, // Snowflake ID, referances an `Item`
 }
 
-#[derive(Debug, FromRow)]
+/// Decoded by hand since `item_id` is read from the `item_id_0` to `item_id_15` columns.
+#[derive(Debug)]
 pub struct Gearset {
     pub id: i64,                   // Snowflake ID, alias of rowid
     pub updated_at: NaiveDateTime, // Unix timestamp in seconds
@@ -147,14 +152,15 @@ pub struct Gearset {
     pub item_id: [Option<i64>; 16],   // Snowflake ID array, referances multiple `Item`s
 }
 
-#[derive(Debug, FromRow)]
+/// Decoded by hand since `item_content_id` is read from the `item_content_id_0` to `item_content_id_15` columns.
+#[derive(Debug)]
 pub struct Outfit {
     pub id: i64,                   // Snowflake ID, alias of rowid
     pub updated_at: NaiveDateTime, // Unix timestamp in seconds
     pub character_id: i64,         // Snowflake ID, referances a `Character`
     pub name: String, // Case insensitive indexed name, special value BASE means this is the default gearset that is directly modified when equipping gear
-    pub customization: Json<Customization>,
-    pub data: Json<OutfitData>,
+    pub customization: Option<Json<Customization>>, // None when the outfit doesn't override the character's customization
+    pub data: Option<Json<OutfitData>>, // None when the outfit has no extra data
     pub item_content_id: [Option<i64>; 16], // Snowflake ID array, referances multiple `Content`s
 }
 
@@ -232,6 +238,8 @@ pub struct Asset {
     pub size: i64,    // Size of data in bytes
     pub is_user_generated: bool,
     pub creator_user_id: Option<i64>, // Should not be exposed to client. Snowflake ID, referances an `User`
+    pub hash: String, // Lowercase hex SHA-256 hash of `data`
+    pub source_file: Option<String>, // Data file this row was imported from, None when user generated
 }
 
 #[derive(Debug, FromRow)]
@@ -250,4 +258,13 @@ pub struct Content {
     pub is_user_generated: bool,
     pub base_content_id: Option<i64>,
     pub creator_user_id: Option<i64>, // Should not be exposed to client. Snowflake ID, referances an `User`
+    pub source_file: Option<String>, // Data file this row was imported from, None when user generated
+}
+
+#[derive(Debug, FromRow)]
+pub struct EquipmentSlot {
+    pub id: i64, // Index of the slot's `Gearset.item_id` and `Outfit.item_content_id`, between 0 and 15
+    pub updated_at: NaiveDateTime, // Unix timestamp in seconds
+    pub name: String,              // Unique no case
+    pub source_file: Option<String>, // Data file this row was imported from, None when created through the API
 }
@@ -0,0 +1,39 @@
+use super::tables::{Gearset, Outfit};
+use sqlx::{FromRow, Row, sqlite::SqliteRow};
+
+impl FromRow<'_, SqliteRow> for Gearset {
+    fn from_row(row: &SqliteRow) -> Result<Self, sqlx::Error> {
+        let mut item_id = [None; 16];
+        for (i, id) in item_id.iter_mut().enumerate() {
+            *id = row.try_get(format!("item_id_{}", i).as_str())?;
+        }
+        Ok(Gearset {
+            id: row.try_get("id")?,
+            updated_at: row.try_get("updated_at")?,
+            character_id: row.try_get("character_id")?,
+            name: row.try_get("name")?,
+            statistics: row.try_get("statistics")?,
+            linked_class_id: row.try_get("linked_class_id")?,
+            linked_outfit_id: row.try_get("linked_outfit_id")?,
+            item_id,
+        })
+    }
+}
+
+impl FromRow<'_, SqliteRow> for Outfit {
+    fn from_row(row: &SqliteRow) -> Result<Self, sqlx::Error> {
+        let mut item_content_id = [None; 16];
+        for (i, id) in item_content_id.iter_mut().enumerate() {
+            *id = row.try_get(format!("item_content_id_{}", i).as_str())?;
+        }
+        Ok(Outfit {
+            id: row.try_get("id")?,
+            updated_at: row.try_get("updated_at")?,
+            character_id: row.try_get("character_id")?,
+            name: row.try_get("name")?,
+            customization: row.try_get("customization")?,
+            data: row.try_get("data")?,
+            item_content_id,
+        })
+    }
+}
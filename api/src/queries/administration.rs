@@ -0,0 +1,313 @@
+use crate::{
+    model::{
+        fields::AccessLevel,
+        tables::{AccessToken, GameServer, World},
+    },
+    utils::{authentication::generate_access_token, current_timestamp, next_id},
+};
+use sonyflake::Sonyflake;
+use sqlx::{Pool, Sqlite, query_as, query_scalar};
+
+/// Creates a new access token. Returns the token itself, which is not stored and can't be retrieved again, along with the stored row.
+pub async fn create_access_token_query(
+    db: &Pool<Sqlite>,
+    sf: &Sonyflake,
+    access_level: AccessLevel,
+    game_server_id: Option<String>,
+    lifetime_seconds: Option<i64>,
+) -> Result<(String, AccessToken), String> {
+    let game_server_id = match access_level {
+        AccessLevel::GameServer => {
+            Some(game_server_id.ok_or("GameServer access tokens require a game_server_id.")?)
+        }
+        _ => None,
+    };
+    let expires_at = match lifetime_seconds {
+        Some(lifetime_seconds) if lifetime_seconds <= 0 => {
+            return Err("lifetime_seconds must be positive.".to_owned());
+        }
+        Some(lifetime_seconds) => Some(current_timestamp() + lifetime_seconds),
+        None => None,
+    };
+
+    let (id, _, _) = next_id(sf)?;
+    let (token, token_hash) = generate_access_token(id, &access_level, game_server_id.as_deref())
+        .ok_or("Failed to generate token.")?;
+    let access_token = query_as::<_, AccessToken>(
+        "INSERT INTO access_token (id, access_token_hash, access_level, game_server_id, expires_at) VALUES ($1, $2, $3, $4, $5) RETURNING *",
+    )
+    .bind(id)
+    .bind(token_hash)
+    .bind(access_level)
+    .bind(game_server_id)
+    .bind(expires_at)
+    .fetch_one(db)
+    .await
+    .map_err(|e| e.to_string())?;
+    Ok((token, access_token))
+}
+
+/// Returns one page of access tokens along with the total number of tokens matching the filters.
+pub async fn get_access_tokens_query(
+    db: &Pool<Sqlite>,
+    page_size: i32,
+    offset: i64,
+    id: Option<i64>,
+    can_expire: Option<bool>,
+) -> Result<(Vec<AccessToken>, i64), String> {
+    let total = query_scalar::<_, i64>(
+        "SELECT COUNT(*) FROM access_token WHERE ($1 IS NULL OR id = $1) AND ($2 IS NULL OR (expires_at IS NOT NULL) = $2)",
+    )
+    .bind(id)
+    .bind(can_expire)
+    .fetch_one(db)
+    .await
+    .map_err(|e| e.to_string())?;
+    let access_tokens = query_as::<_, AccessToken>(
+        "SELECT * FROM access_token WHERE ($1 IS NULL OR id = $1) AND ($2 IS NULL OR (expires_at IS NOT NULL) = $2) ORDER BY id LIMIT $3 OFFSET $4",
+    )
+    .bind(id)
+    .bind(can_expire)
+    .bind(page_size)
+    .bind(offset)
+    .fetch_all(db)
+    .await
+    .map_err(|e| e.to_string())?;
+    Ok((access_tokens, total))
+}
+
+pub async fn delete_access_token_query(db: &Pool<Sqlite>, id: i64) -> Result<bool, String> {
+    let result = sqlx::query("DELETE FROM access_token WHERE id = $1")
+        .bind(id)
+        .execute(db)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn create_game_server_query(
+    db: &Pool<Sqlite>,
+    id: &str,
+    region_code: &str,
+    display_name: &str,
+) -> Result<GameServer, String> {
+    query_as::<_, GameServer>(
+        "INSERT INTO game_server (id, region_code, display_name) VALUES ($1, $2, $3) RETURNING *",
+    )
+    .bind(id)
+    .bind(region_code)
+    .bind(display_name)
+    .fetch_one(db)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Updates the provided fields of a game server. Fields that are `None` are left unchanged.
+pub async fn update_game_server_query(
+    db: &Pool<Sqlite>,
+    id: &str,
+    region_code: Option<&str>,
+    display_name: Option<&str>,
+) -> Result<GameServer, String> {
+    query_as::<_, GameServer>(
+        "UPDATE game_server SET region_code = COALESCE($2, region_code), display_name = COALESCE($3, display_name), updated_at = (unixepoch()) WHERE id = $1 RETURNING *",
+    )
+    .bind(id)
+    .bind(region_code)
+    .bind(display_name)
+    .fetch_one(db)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+pub async fn get_game_server_query(db: &Pool<Sqlite>, id: &str) -> Result<GameServer, String> {
+    query_as::<_, GameServer>("SELECT * FROM game_server WHERE id = $1")
+        .bind(id)
+        .fetch_one(db)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Returns one page of game servers along with the total number of game servers.
+pub async fn get_game_servers_query(
+    db: &Pool<Sqlite>,
+    page_size: i32,
+    offset: i64,
+) -> Result<(Vec<GameServer>, i64), String> {
+    let total = query_scalar::<_, i64>("SELECT COUNT(*) FROM game_server")
+        .fetch_one(db)
+        .await
+        .map_err(|e| e.to_string())?;
+    let game_servers =
+        query_as::<_, GameServer>("SELECT * FROM game_server ORDER BY id LIMIT $1 OFFSET $2")
+            .bind(page_size)
+            .bind(offset)
+            .fetch_all(db)
+            .await
+            .map_err(|e| e.to_string())?;
+    Ok((game_servers, total))
+}
+
+/// Deletes a game server along with the `GameServer` access tokens issued for it. Fails while any world is still hosted on it.
+pub async fn retire_game_server_query(db: &Pool<Sqlite>, id: &str) -> Result<u64, String> {
+    let mut tx = db.begin().await.map_err(|e| e.to_string())?;
+    let access_tokens = sqlx::query("DELETE FROM access_token WHERE game_server_id = $1")
+        .bind(id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+    sqlx::query("DELETE FROM game_server WHERE id = $1")
+        .bind(id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+    tx.commit().await.map_err(|e| e.to_string())?;
+    Ok(access_tokens.rows_affected())
+}
+
+pub async fn create_world_query(
+    db: &Pool<Sqlite>,
+    id: &str,
+    game_server_id: &str,
+    display_name: &str,
+) -> Result<World, String> {
+    query_as::<_, World>(
+        "INSERT INTO world (id, game_server_id, display_name) VALUES ($1, $2, $3) RETURNING *",
+    )
+    .bind(id)
+    .bind(game_server_id)
+    .bind(display_name)
+    .fetch_one(db)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Updates the provided fields of a world. Fields that are `None` are left unchanged.
+pub async fn update_world_query(
+    db: &Pool<Sqlite>,
+    id: &str,
+    game_server_id: Option<&str>,
+    display_name: Option<&str>,
+) -> Result<World, String> {
+    query_as::<_, World>(
+        "UPDATE world SET game_server_id = COALESCE($2, game_server_id), display_name = COALESCE($3, display_name), updated_at = (unixepoch()) WHERE id = $1 RETURNING *",
+    )
+    .bind(id)
+    .bind(game_server_id)
+    .bind(display_name)
+    .fetch_one(db)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+pub async fn get_world_query(db: &Pool<Sqlite>, id: &str) -> Result<World, String> {
+    query_as::<_, World>("SELECT * FROM world WHERE id = $1")
+        .bind(id)
+        .fetch_one(db)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Returns one page of worlds, optionally filtered by game server, along with the total number of matching worlds.
+pub async fn get_worlds_query(
+    db: &Pool<Sqlite>,
+    page_size: i32,
+    offset: i64,
+    game_server_id: Option<&str>,
+) -> Result<(Vec<World>, i64), String> {
+    let total = query_scalar::<_, i64>(
+        "SELECT COUNT(*) FROM world WHERE ($1 IS NULL OR game_server_id = $1)",
+    )
+    .bind(game_server_id)
+    .fetch_one(db)
+    .await
+    .map_err(|e| e.to_string())?;
+    let worlds = query_as::<_, World>(
+        "SELECT * FROM world WHERE ($1 IS NULL OR game_server_id = $1) ORDER BY id LIMIT $2 OFFSET $3",
+    )
+    .bind(game_server_id)
+    .bind(page_size)
+    .bind(offset)
+    .fetch_all(db)
+    .await
+    .map_err(|e| e.to_string())?;
+    Ok((worlds, total))
+}
+
+pub async fn count_game_server_worlds_query(
+    db: &Pool<Sqlite>,
+    game_server_id: &str,
+) -> Result<i64, String> {
+    query_scalar::<_, i64>("SELECT COUNT(*) FROM world WHERE game_server_id = $1")
+        .bind(game_server_id)
+        .fetch_one(db)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Returns the number of characters and guilds that have the world as their home world.
+pub async fn count_world_residents_query(
+    db: &Pool<Sqlite>,
+    world_id: &str,
+) -> Result<(i64, i64), String> {
+    let characters =
+        query_scalar::<_, i64>("SELECT COUNT(*) FROM character WHERE home_world_id = $1")
+            .bind(world_id)
+            .fetch_one(db)
+            .await
+            .map_err(|e| e.to_string())?;
+    let guilds = query_scalar::<_, i64>("SELECT COUNT(*) FROM guild WHERE home_world_id = $1")
+        .bind(world_id)
+        .fetch_one(db)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok((characters, guilds))
+}
+
+/// Deletes a world, first moving its characters and guilds to `migrate_to_world_id` if provided. Returns the number of migrated characters and guilds.
+/// Fails without changes if a migrated name is already taken in the target world.
+pub async fn retire_world_query(
+    db: &Pool<Sqlite>,
+    id: &str,
+    migrate_to_world_id: Option<&str>,
+) -> Result<(u64, u64), String> {
+    let mut tx = db.begin().await.map_err(|e| e.to_string())?;
+    let (mut characters, mut guilds) = (0, 0);
+    if let Some(migrate_to_world_id) = migrate_to_world_id {
+        characters = sqlx::query(
+            "UPDATE character SET home_world_id = $2, updated_at = (unixepoch()) WHERE home_world_id = $1",
+        )
+        .bind(id)
+        .bind(migrate_to_world_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| match e.as_database_error() {
+            Some(db_e) if db_e.is_unique_violation() => {
+                "A character name is already taken in the target world.".to_owned()
+            }
+            _ => e.to_string(),
+        })?
+        .rows_affected();
+        guilds = sqlx::query(
+            "UPDATE guild SET home_world_id = $2, updated_at = (unixepoch()) WHERE home_world_id = $1",
+        )
+        .bind(id)
+        .bind(migrate_to_world_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| match e.as_database_error() {
+            Some(db_e) if db_e.is_unique_violation() => {
+                "A guild name is already taken in the target world.".to_owned()
+            }
+            _ => e.to_string(),
+        })?
+        .rows_affected();
+    }
+    sqlx::query("DELETE FROM world WHERE id = $1")
+        .bind(id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+    tx.commit().await.map_err(|e| e.to_string())?;
+    Ok((characters, guilds))
+}
@@ -0,0 +1,474 @@
+use crate::{
+    model::{
+        AssetHeader, ContentSync, Ref, UploadError,
+        fields::{AssetData, ContentData, ContentSubtype, ContentType},
+        tables::{Asset, Content},
+    },
+    utils::{blob::read_blob, next_id},
+};
+use sonyflake::Sonyflake;
+use sqlx::{Pool, Sqlite, query_as, query_scalar, types::Json};
+
+/// Gets content by id, or by name optionally narrowed down by `content_type` since names are only unique per type.
+pub async fn get_content_query(
+    db: &Pool<Sqlite>,
+    content_ref: Ref,
+    content_type: Option<ContentType>,
+) -> Result<Content, String> {
+    let mut contents = match content_ref {
+        Ref::Id(id) => query_as::<_, Content>("SELECT * FROM content WHERE id = $1")
+            .bind(id)
+            .fetch_all(db)
+            .await
+            .map_err(|e| e.to_string())?,
+        Ref::Name(name) => query_as::<_, Content>(
+            "SELECT * FROM content WHERE name = $1 AND ($2 IS NULL OR content_type = $2) LIMIT 2",
+        )
+        .bind(name)
+        .bind(content_type)
+        .fetch_all(db)
+        .await
+        .map_err(|e| e.to_string())?,
+    };
+    if contents.len() > 1 {
+        return Err("Multiple contents share that name, provide a content_type.".to_owned());
+    }
+    contents.pop().ok_or("Content not found.".to_owned())
+}
+
+/// Fetches the base of `content` and merges them with `resolve_content`.
+pub async fn resolve_content_query(db: &Pool<Sqlite>, content: Content) -> Result<Content, String> {
+    let base = match content.base_content_id {
+        Some(base_content_id) => Some(
+            query_as::<_, Content>("SELECT * FROM content WHERE id = $1")
+                .bind(base_content_id)
+                .fetch_optional(db)
+                .await
+                .map_err(|e| e.to_string())?
+                .ok_or(format!(
+                    "Base content {} of content {} not found.",
+                    base_content_id, content.id
+                ))?,
+        ),
+        None => None,
+    };
+    resolve_content(content, base)
+}
+
+/// Merges user generated `content` over its `base`, the non user generated content it inherits from, into the effective definition.
+/// - `id`, `name`, `is_user_generated`, `base_content_id`, `creator_user_id` and `source_file` come from `content`.
+/// - `content_type` and `content_subtype` come from `base`.
+/// - Each asset slot set on `content` replaces the same slot on `base`, empty slots fall back to `base`.
+/// - `data` comes from `content` when its type carries data, retagged as the type of `base`, otherwise from `base`.
+/// - `updated_at` is the later of the two so caches notice when either changes.
+///
+/// Content that isn't user generated is returned as is. Inheritance is one level deep, so cycles, bases that are user generated or inherit from other content, and mismatched types are rejected.
+pub fn resolve_content(content: Content, base: Option<Content>) -> Result<Content, String> {
+    let base = match (content.is_user_generated, base) {
+        (false, _) if content.base_content_id.is_some() => {
+            return Err(format!(
+                "Content {} isn't user generated but has a base_content_id.",
+                content.id
+            ));
+        }
+        (false, _) => return Ok(content),
+        (true, Some(base)) if Some(base.id) == content.base_content_id => base,
+        (true, _) => {
+            return Err(format!(
+                "User generated content {} is missing its base content.",
+                content.id
+            ));
+        }
+    };
+    if base.id == content.id {
+        return Err(format!("Content {} inherits from itself.", content.id));
+    }
+    // Also rejects content inheriting from each other, since the base would have a base of its own.
+    if base.is_user_generated || base.base_content_id.is_some() {
+        return Err(format!(
+            "Content {} inherits from {}, which is user generated or inherits from other content. Only one level of inheritance is allowed.",
+            content.id, base.id
+        ));
+    }
+    let data = match (content.data.0, base.data.0) {
+        (ContentData::UserGeneratedItem(data), ContentData::Item(_)) => ContentData::Item(data),
+        (ContentData::UserGeneratedOption {}, data @ ContentData::CharacterOption {}) => data,
+        (data, base_data) => {
+            return Err(format!(
+                "Content {} of type {:?} can't inherit from content {} of type {:?}.",
+                content.id,
+                data.content_type(),
+                base.id,
+                base_data.content_type()
+            ));
+        }
+    };
+    Ok(Content {
+        id: content.id,
+        updated_at: content.updated_at.max(base.updated_at),
+        name: content.name,
+        content_type: base.content_type,
+        content_subtype: base.content_subtype,
+        data: Json(data),
+        asset_id_0: content.asset_id_0.or(base.asset_id_0),
+        asset_id_1: content.asset_id_1.or(base.asset_id_1),
+        asset_id_2: content.asset_id_2.or(base.asset_id_2),
+        asset_id_3: content.asset_id_3.or(base.asset_id_3),
+        asset_id_4: content.asset_id_4.or(base.asset_id_4),
+        is_user_generated: true,
+        base_content_id: Some(base.id),
+        creator_user_id: content.creator_user_id,
+        source_file: content.source_file,
+    })
+}
+
+/// Returns one page of content along with the total number of contents matching the filters.
+pub async fn get_contents_query(
+    db: &Pool<Sqlite>,
+    page_size: i32,
+    offset: i64,
+    content_type: Option<ContentType>,
+    content_subtype: Option<ContentSubtype>,
+) -> Result<(Vec<Content>, i64), String> {
+    let total = query_scalar::<_, i64>(
+        "SELECT COUNT(*) FROM content WHERE ($1 IS NULL OR content_type = $1) AND ($2 IS NULL OR content_subtype = $2)",
+    )
+    .bind(&content_type)
+    .bind(&content_subtype)
+    .fetch_one(db)
+    .await
+    .map_err(|e| e.to_string())?;
+    let contents = query_as::<_, Content>(
+        "SELECT * FROM content WHERE ($1 IS NULL OR content_type = $1) AND ($2 IS NULL OR content_subtype = $2) ORDER BY id LIMIT $3 OFFSET $4",
+    )
+    .bind(content_type)
+    .bind(content_subtype)
+    .bind(page_size)
+    .bind(offset)
+    .fetch_all(db)
+    .await
+    .map_err(|e| e.to_string())?;
+    Ok((contents, total))
+}
+
+/// Gets an asset by id or by its virtual filesystem `path`.
+/// When `if_none_match` equals `Asset.hash` the data isn't read and `Asset.data` is returned as an empty blob. An empty hash is unknown and never matches.
+pub async fn get_asset_query(
+    db: &Pool<Sqlite>,
+    asset_ref: Ref,
+    if_none_match: Option<&str>,
+) -> Result<Asset, String> {
+    let query = match asset_ref {
+        Ref::Id(id) => query_as::<_, Asset>(
+            "SELECT id, updated_at, path, file_type, CASE WHEN hash != '' AND hash IS $2 THEN X'' ELSE data END AS data, size, is_user_generated, creator_user_id, hash, source_file FROM asset WHERE id = $1",
+        )
+        .bind(id),
+        Ref::Name(path) => query_as::<_, Asset>(
+            "SELECT id, updated_at, path, file_type, CASE WHEN hash != '' AND hash IS $2 THEN X'' ELSE data END AS data, size, is_user_generated, creator_user_id, hash, source_file FROM asset WHERE path = $1",
+        )
+        .bind(path),
+    };
+    query
+        .bind(if_none_match)
+        .fetch_one(db)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Returns one page of assets whose path starts with `path_prefix` along with the total number of matching assets.
+/// When `include_data` is false `Asset.data` is returned as an empty blob instead of being read.
+pub async fn get_assets_query(
+    db: &Pool<Sqlite>,
+    page_size: i32,
+    offset: i64,
+    path_prefix: Option<&str>,
+    include_data: bool,
+) -> Result<(Vec<Asset>, i64), String> {
+    let path_pattern = path_prefix.map(|path_prefix| {
+        format!(
+            "{}%",
+            path_prefix
+                .replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_")
+        )
+    });
+    let total = query_scalar::<_, i64>(
+        "SELECT COUNT(*) FROM asset WHERE ($1 IS NULL OR path LIKE $1 ESCAPE '\\')",
+    )
+    .bind(&path_pattern)
+    .fetch_one(db)
+    .await
+    .map_err(|e| e.to_string())?;
+    let assets = query_as::<_, Asset>(
+        "SELECT id, updated_at, path, file_type, CASE WHEN $2 THEN data ELSE X'' END AS data, size, is_user_generated, creator_user_id, hash, source_file FROM asset WHERE ($1 IS NULL OR path LIKE $1 ESCAPE '\\') ORDER BY path LIMIT $3 OFFSET $4",
+    )
+    .bind(path_pattern)
+    .bind(include_data)
+    .bind(page_size)
+    .bind(offset)
+    .fetch_all(db)
+    .await
+    .map_err(|e| e.to_string())?;
+    Ok((assets, total))
+}
+
+/// Gets an asset's metadata without reading its data.
+pub async fn get_asset_header_query(
+    db: &Pool<Sqlite>,
+    asset_ref: Ref,
+) -> Result<AssetHeader, String> {
+    let query = match asset_ref {
+        Ref::Id(id) => query_as::<_, AssetHeader>(
+            "SELECT id, path, file_type, size, hash FROM asset WHERE id = $1",
+        )
+        .bind(id),
+        Ref::Name(path) => query_as::<_, AssetHeader>(
+            "SELECT id, path, file_type, size, hash FROM asset WHERE path = $1",
+        )
+        .bind(path),
+    };
+    query.fetch_one(db).await.map_err(|e| e.to_string())
+}
+
+/// Reads up to `length` bytes of `Asset.data` starting at `offset`. Returns the bytes read along with the total size of the data.
+pub async fn read_asset_data_query(
+    db: &Pool<Sqlite>,
+    asset_id: i64,
+    offset: i64,
+    length: usize,
+) -> Result<(Vec<u8>, i64), String> {
+    let mut conn = db.acquire().await.map_err(|e| e.to_string())?;
+    let mut handle = conn.lock_handle().await.map_err(|e| e.to_string())?;
+    read_blob(
+        handle.as_raw_handle(),
+        "asset",
+        "data",
+        asset_id,
+        offset,
+        length,
+    )
+}
+
+/// Saves a user generated asset at `path`, replacing the user's own asset if one is already there. `file` is the `(data, size, file_type, hash)` returned by `read_asset_data`.
+/// Fails when the user's assets, not counting the one being replaced, would take up more than `quota` bytes. The returned `Asset.data` is empty.
+pub async fn upload_asset_query(
+    db: &Pool<Sqlite>,
+    sf: &Sonyflake,
+    creator_user_id: i64,
+    path: String,
+    file: (AssetData, i64, String, String),
+    quota: i64,
+) -> Result<Asset, UploadError> {
+    let (data, size, file_type, hash) = file;
+    let mut tx = db
+        .begin()
+        .await
+        .map_err(|e| UploadError::Internal(e.to_string()))?;
+    let used = query_scalar::<_, i64>(
+        "SELECT COALESCE(SUM(size), 0) FROM asset WHERE creator_user_id = $1 AND path != $2",
+    )
+    .bind(creator_user_id)
+    .bind(&path)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| UploadError::Internal(e.to_string()))?;
+    if used + size > quota {
+        return Err(UploadError::QuotaExceeded { used, quota });
+    }
+
+    let (id, _, _) = next_id(sf).map_err(UploadError::Internal)?;
+    let asset = query_as::<_, Asset>(
+            "INSERT INTO asset (id, path, file_type, data, size, hash, is_user_generated, creator_user_id) VALUES ($1, $2, $3, $4, $5, $6, TRUE, $7) ON CONFLICT(path) DO UPDATE SET file_type=excluded.file_type, data=excluded.data, size=excluded.size, hash=excluded.hash, updated_at=(unixepoch()) WHERE asset.creator_user_id IS excluded.creator_user_id RETURNING id, updated_at, path, file_type, X'' AS data, size, is_user_generated, creator_user_id, hash, source_file",
+        )
+        .bind(id)
+        .bind(path)
+        .bind(file_type)
+        .bind(data)
+        .bind(size)
+        .bind(hash)
+        .bind(creator_user_id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| UploadError::Internal(e.to_string()))?
+        .ok_or(UploadError::PathInUse)?;
+    tx.commit()
+        .await
+        .map_err(|e| UploadError::Internal(e.to_string()))?;
+    Ok(asset)
+}
+
+/// Gets every `Content`, non user generated `Asset`, and tombstone changed at or after `since`, read in a single transaction so the result is consistent.
+/// When `include_asset_data` is false `Asset.data` is returned as an empty blob instead of being read.
+/// No tombstones are returned when `since` is 0 since a full sync replaces the whole cache.
+pub async fn sync_content_query(
+    db: &Pool<Sqlite>,
+    since: i64,
+    include_asset_data: bool,
+) -> Result<ContentSync, String> {
+    let mut tx = db.begin().await.map_err(|e| e.to_string())?;
+    let contents =
+        query_as::<_, Content>("SELECT * FROM content WHERE updated_at >= $1 ORDER BY id")
+            .bind(since)
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+    let assets = query_as::<_, Asset>(
+        "SELECT id, updated_at, path, file_type, CASE WHEN $2 THEN data ELSE X'' END AS data, size, is_user_generated, creator_user_id, hash, source_file FROM asset WHERE updated_at >= $1 AND is_user_generated = FALSE ORDER BY id",
+    )
+    .bind(since)
+    .bind(include_asset_data)
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|e| e.to_string())?;
+    let deleted_content_ids = query_scalar::<_, i64>(
+        "SELECT id FROM content_tombstone WHERE $1 > 0 AND deleted_at >= $1 ORDER BY id",
+    )
+    .bind(since)
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|e| e.to_string())?;
+    let deleted_asset_ids = query_scalar::<_, i64>(
+        "SELECT id FROM asset_tombstone WHERE $1 > 0 AND deleted_at >= $1 ORDER BY id",
+    )
+    .bind(since)
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|e| e.to_string())?;
+    tx.commit().await.map_err(|e| e.to_string())?;
+    Ok(ContentSync {
+        contents,
+        assets,
+        deleted_content_ids,
+        deleted_asset_ids,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::fields::{ItemContentData, ItemTradability};
+    use chrono::DateTime;
+    use proptest::prelude::*;
+
+    fn item_data(stack_size: i64) -> ItemContentData {
+        ItemContentData {
+            stack_size,
+            is_unique: false,
+            is_soulbound: false,
+            tradability: ItemTradability::PlayerMarketable,
+            capacity: None,
+            slot: Some(0),
+        }
+    }
+
+    fn content(
+        id: i64,
+        data: ContentData,
+        is_user_generated: bool,
+        base_content_id: Option<i64>,
+    ) -> Content {
+        Content {
+            id,
+            updated_at: DateTime::UNIX_EPOCH.naive_utc(),
+            name: format!("Content {}", id),
+            content_type: data.content_type(),
+            content_subtype: ContentSubtype::None,
+            data: Json(data),
+            asset_id_0: None,
+            asset_id_1: None,
+            asset_id_2: None,
+            asset_id_3: None,
+            asset_id_4: None,
+            is_user_generated,
+            base_content_id,
+            creator_user_id: is_user_generated.then_some(1),
+            source_file: None,
+        }
+    }
+
+    fn user_generated_item(id: i64, base_content_id: i64) -> Content {
+        content(
+            id,
+            ContentData::UserGeneratedItem(item_data(1)),
+            true,
+            Some(base_content_id),
+        )
+    }
+
+    fn assets() -> impl Strategy<Value = [Option<i64>; 5]> {
+        prop::array::uniform5(prop::option::of(1i64..100))
+    }
+
+    proptest! {
+        #[test]
+        fn self_reference_is_rejected(id in 1i64..1000) {
+            let result = resolve_content(user_generated_item(id, id), Some(user_generated_item(id, id)));
+            prop_assert!(result.is_err());
+        }
+
+        #[test]
+        fn mutual_inheritance_is_rejected(id in 1i64..1000, base_id in 1000i64..2000) {
+            let result = resolve_content(user_generated_item(id, base_id), Some(user_generated_item(base_id, id)));
+            prop_assert!(result.is_err());
+        }
+
+        #[test]
+        fn user_generated_base_is_rejected(id in 1i64..1000, base_id in 1000i64..2000) {
+            let base = content(base_id, ContentData::UserGeneratedItem(item_data(1)), true, None);
+            prop_assert!(resolve_content(user_generated_item(id, base_id), Some(base)).is_err());
+        }
+
+        #[test]
+        fn base_with_its_own_base_is_rejected(id in 1i64..1000, base_id in 1000i64..2000, root_id in 2000i64..3000) {
+            let base = content(base_id, ContentData::Item(item_data(1)), false, Some(root_id));
+            prop_assert!(resolve_content(user_generated_item(id, base_id), Some(base)).is_err());
+        }
+
+        #[test]
+        fn mismatched_base_type_is_rejected(id in 1i64..1000, base_id in 1000i64..2000) {
+            let base = content(base_id, ContentData::CharacterOption {}, false, None);
+            prop_assert!(resolve_content(user_generated_item(id, base_id), Some(base)).is_err());
+        }
+
+        #[test]
+        fn base_content_id_without_user_generated_is_rejected(id in 1i64..1000, base_id in 1000i64..2000) {
+            let row = content(id, ContentData::Item(item_data(1)), false, Some(base_id));
+            let base = content(base_id, ContentData::Item(item_data(1)), false, None);
+            prop_assert!(resolve_content(row, Some(base)).is_err());
+        }
+
+        #[test]
+        fn user_generated_item_overrides_item(
+            id in 1i64..1000,
+            base_id in 1000i64..2000,
+            stack_size in 1i64..100,
+            asset_ids in assets(),
+            base_asset_ids in assets(),
+            updated_at in 0i64..1_000_000,
+            base_updated_at in 0i64..1_000_000,
+        ) {
+            let mut row = content(id, ContentData::UserGeneratedItem(item_data(stack_size)), true, Some(base_id));
+            [row.asset_id_0, row.asset_id_1, row.asset_id_2, row.asset_id_3, row.asset_id_4] = asset_ids;
+            row.updated_at = DateTime::from_timestamp(updated_at, 0).unwrap().naive_utc();
+            let mut base = content(base_id, ContentData::Item(item_data(1)), false, None);
+            base.content_subtype = ContentSubtype::Equipment;
+            [base.asset_id_0, base.asset_id_1, base.asset_id_2, base.asset_id_3, base.asset_id_4] = base_asset_ids;
+            base.updated_at = DateTime::from_timestamp(base_updated_at, 0).unwrap().naive_utc();
+
+            let resolved = resolve_content(row, Some(base)).unwrap();
+            prop_assert_eq!(resolved.id, id);
+            prop_assert_eq!(resolved.base_content_id, Some(base_id));
+            prop_assert!(resolved.is_user_generated);
+            prop_assert_eq!(resolved.content_type, ContentType::Item);
+            prop_assert_eq!(resolved.content_subtype, ContentSubtype::Equipment);
+            // Data is retagged as the base's type but keeps the user generated values.
+            prop_assert!(matches!(&resolved.data.0, ContentData::Item(data) if data.stack_size == stack_size));
+            let resolved_asset_ids = [resolved.asset_id_0, resolved.asset_id_1, resolved.asset_id_2, resolved.asset_id_3, resolved.asset_id_4];
+            for ((resolved, own), base) in resolved_asset_ids.iter().zip(asset_ids).zip(base_asset_ids) {
+                prop_assert_eq!(*resolved, own.or(base));
+            }
+            prop_assert_eq!(resolved.updated_at.and_utc().timestamp(), updated_at.max(base_updated_at));
+        }
+    }
+}
@@ -1,5 +1,8 @@
 use crate::{
-    model::tables::{AccessToken, User, UserPassword, UserRecoveryCode, UserSession},
+    model::{
+        authentication::AuthError,
+        tables::{AccessToken, User, UserPassword, UserRecoveryCode, UserSession},
+    },
     utils::{
         authentication::{
             generate_session_token, hash_token, parse_access_token_id, verify_password,
@@ -10,7 +13,9 @@ use crate::{
 };
 use sqlx::{query_as, Pool, Sqlite};
 
-const DEFAULT_LIFETIME: i64 = 60 * 60 * 24 * 14;
+pub const DEFAULT_LIFETIME: i64 = 60 * 60 * 24 * 14;
+/// Sessions with less than this many seconds left are renewed when used.
+const SESSION_RENEWAL_THRESHOLD: i64 = DEFAULT_LIFETIME / 2;
 
 pub async fn user_login_query(
     db: &Pool<Sqlite>,
@@ -22,18 +27,9 @@ pub async fn user_login_query(
         .fetch_one(db)
         .await
         .map_err(|_e| ())?;
-    let stored_password =
-        query_as::<_, UserPassword>("SELECT * FROM user_password WHERE user_id = $1")
-            .bind(user.id)
-            .fetch_one(db)
-            .await
-            .map_err(|_e| ())?;
-    if verify_password(password, &stored_password.password_hash) {
-        let session_token = create_user_session_query(db, user.id, DEFAULT_LIFETIME).await?;
-        Ok((session_token, user))
-    } else {
-        Err(())
-    }
+    check_user_password_query(db, user.id, password).await?;
+    let session_token = create_user_session_query(db, user.id, DEFAULT_LIFETIME).await?;
+    Ok((session_token, user))
 }
 
 pub async fn create_user_session_query(
@@ -55,21 +51,37 @@ pub async fn create_user_session_query(
     Ok(session_token)
 }
 
+/// Rejects expired sessions and slides the expiry of sessions that are past `SESSION_RENEWAL_THRESHOLD` forward by `DEFAULT_LIFETIME`.
 pub async fn validate_session_query(
     db: &Pool<Sqlite>,
     session_token: &str,
-) -> Result<(User, UserSession), ()> {
-    let session_token_hash = hash_token(session_token).ok_or(())?;
-    let session = query_as::<_, UserSession>("SELECT * FROM user_session WHERE id = $1")
+) -> Result<(User, UserSession), AuthError> {
+    let session_token_hash = hash_token(session_token).ok_or(AuthError::Invalid)?;
+    let mut session = query_as::<_, UserSession>("SELECT * FROM user_session WHERE id = $1")
         .bind(session_token_hash)
         .fetch_one(db)
         .await
-        .map_err(|_e| ())?;
+        .map_err(|_e| AuthError::Invalid)?;
+    let now = current_timestamp();
+    let expires_at = session.expires_at.and_utc().timestamp();
+    if expires_at <= now {
+        return Err(AuthError::Expired);
+    }
+    if expires_at - now < SESSION_RENEWAL_THRESHOLD {
+        session = query_as::<_, UserSession>(
+            "UPDATE user_session SET expires_at = $1 WHERE id = $2 RETURNING *",
+        )
+        .bind(now + DEFAULT_LIFETIME)
+        .bind(&session.id)
+        .fetch_one(db)
+        .await
+        .map_err(|_e| AuthError::Invalid)?;
+    }
     let user = query_as::<_, User>("SELECT * FROM user WHERE id = $1")
         .bind(session.user_id)
         .fetch_one(db)
         .await
-        .map_err(|_e| ())?;
+        .map_err(|_e| AuthError::Invalid)?;
     Ok((user, session))
 }
 
@@ -94,16 +106,77 @@ pub async fn validate_recovery_code_query(
 pub async fn validate_access_token_query(
     db: &Pool<Sqlite>,
     access_token: &str,
-) -> Result<AccessToken, ()> {
-    let id = parse_access_token_id(access_token).ok_or(())?;
+) -> Result<AccessToken, AuthError> {
+    let id = parse_access_token_id(access_token).ok_or(AuthError::Invalid)?;
     let access = query_as::<_, AccessToken>("SELECT * FROM access_token WHERE id = $1")
         .bind(id)
         .fetch_one(db)
         .await
-        .map_err(|_e| ())?;
-    if verify_token(access_token, &access.access_token_hash) {
-        Ok(access)
+        .map_err(|_e| AuthError::Invalid)?;
+    if !verify_token(access_token, &access.access_token_hash) {
+        return Err(AuthError::Invalid);
+    }
+    match access.expires_at {
+        Some(expires_at) if expires_at <= current_timestamp() => Err(AuthError::Expired),
+        _ => Ok(access),
+    }
+}
+
+pub async fn check_user_password_query(
+    db: &Pool<Sqlite>,
+    user_id: i64,
+    password: &str,
+) -> Result<(), ()> {
+    let stored_password =
+        query_as::<_, UserPassword>("SELECT * FROM user_password WHERE user_id = $1")
+            .bind(user_id)
+            .fetch_one(db)
+            .await
+            .map_err(|_e| ())?;
+    if verify_password(password, &stored_password.password_hash) {
+        Ok(())
     } else {
         Err(())
     }
 }
+
+pub async fn delete_user_session_query(db: &Pool<Sqlite>, session_id: &str) -> Result<(), String> {
+    sqlx::query("DELETE FROM user_session WHERE id = $1")
+        .bind(session_id)
+        .execute(db)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Deletes every session belonging to a user except for `keep_session_id` if it is provided.
+pub async fn delete_user_sessions_query(
+    db: &Pool<Sqlite>,
+    user_id: i64,
+    keep_session_id: Option<&str>,
+) -> Result<u64, String> {
+    let result = sqlx::query("DELETE FROM user_session WHERE user_id = $1 AND id IS NOT $2")
+        .bind(user_id)
+        .bind(keep_session_id)
+        .execute(db)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(result.rows_affected())
+}
+
+/// Deletes every `user_session` and `access_token` that has expired. Returns the number of deleted sessions and access tokens.
+pub async fn delete_expired_credentials_query(db: &Pool<Sqlite>) -> Result<(u64, u64), String> {
+    let now = current_timestamp();
+    let sessions = sqlx::query("DELETE FROM user_session WHERE expires_at <= $1")
+        .bind(now)
+        .execute(db)
+        .await
+        .map_err(|e| e.to_string())?;
+    let access_tokens =
+        sqlx::query("DELETE FROM access_token WHERE expires_at IS NOT NULL AND expires_at <= $1")
+            .bind(now)
+            .execute(db)
+            .await
+            .map_err(|e| e.to_string())?;
+    Ok((sessions.rows_affected(), access_tokens.rows_affected()))
+}
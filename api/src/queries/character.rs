@@ -1,14 +1,16 @@
 use crate::{
     model::{
         fields::Role,
-        tables::{Character, User},
+        tables::{Character, CharacterStatus, User},
+        GameDataError,
     },
     utils::{
         authentication::get_random_id, generate_random_name, next_id, validate_and_format_name,
+        BASE_CLASS_CONTENT_ID, BASE_SET_NAME,
     },
 };
 use sonyflake::Sonyflake;
-use sqlx::{query_as, Pool, Sqlite};
+use sqlx::{query_as, query_scalar, Pool, Sqlite, SqliteConnection};
 
 pub async fn create_character_query(
     db: &Pool<Sqlite>,
@@ -35,7 +37,7 @@ pub async fn create_character_query(
         None => user.role,
     };
 
-    let (id, created_at, _) = next_id(&sf)?;
+    let (id, created_at, _) = next_id(sf)?;
     // TODO poll for uniqueness
     let handle = get_random_id();
     let new_character = query_as::<_, Character>(
@@ -54,3 +56,75 @@ pub async fn create_character_query(
 
     Ok(new_character)
 }
+
+/// Gets a character's status, creating it along with the character's BaseClass, BASE gearset and BASE outfit the first time it's needed.
+/// The BASE gearset is linked to the BaseClass.
+pub async fn get_or_create_character_status(
+    conn: &mut SqliteConnection,
+    sf: &Sonyflake,
+    character_id: i64,
+) -> Result<CharacterStatus, GameDataError> {
+    let status =
+        query_as::<_, CharacterStatus>("SELECT * FROM character_status WHERE character_id = $1")
+            .bind(character_id)
+            .fetch_optional(&mut *conn)
+            .await
+            .map_err(|e| GameDataError::Internal(e.to_string()))?;
+    if let Some(status) = status {
+        return Ok(status);
+    }
+    let is_character = sqlx::query("SELECT id FROM character WHERE id = $1")
+        .bind(character_id)
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|e| GameDataError::Internal(e.to_string()))?
+        .is_some();
+    if !is_character {
+        return Err(GameDataError::NotFound("Character not found.".to_owned()));
+    }
+
+    // Rows left behind by an earlier partial setup are reused.
+    let (id, _, _) = next_id(sf).map_err(GameDataError::Internal)?;
+    let class_id = query_scalar::<_, i64>(
+        "INSERT INTO class (id, character_id, class_content_id) VALUES ($1, $2, $3) ON CONFLICT(character_id, class_content_id) DO UPDATE SET updated_at = updated_at RETURNING id",
+    )
+    .bind(id)
+    .bind(character_id)
+    .bind(BASE_CLASS_CONTENT_ID)
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(|e| GameDataError::Internal(e.to_string()))?;
+    let (id, _, _) = next_id(sf).map_err(GameDataError::Internal)?;
+    let gearset_id = query_scalar::<_, i64>(
+        "INSERT INTO gearset (id, character_id, name, linked_class_id) VALUES ($1, $2, $3, $4) ON CONFLICT(character_id, name) DO UPDATE SET updated_at = updated_at RETURNING id",
+    )
+    .bind(id)
+    .bind(character_id)
+    .bind(BASE_SET_NAME)
+    .bind(class_id)
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(|e| GameDataError::Internal(e.to_string()))?;
+    let (id, _, _) = next_id(sf).map_err(GameDataError::Internal)?;
+    let outfit_id = query_scalar::<_, i64>(
+        "INSERT INTO outfit (id, character_id, name) VALUES ($1, $2, $3) ON CONFLICT(character_id, name) DO UPDATE SET updated_at = updated_at RETURNING id",
+    )
+    .bind(id)
+    .bind(character_id)
+    .bind(BASE_SET_NAME)
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(|e| GameDataError::Internal(e.to_string()))?;
+    let (id, _, _) = next_id(sf).map_err(GameDataError::Internal)?;
+    query_as::<_, CharacterStatus>(
+        "INSERT INTO character_status (id, character_id, active_class_id, base_gearset_id, base_outfit_id) VALUES ($1, $2, $3, $4, $5) RETURNING *",
+    )
+    .bind(id)
+    .bind(character_id)
+    .bind(class_id)
+    .bind(gearset_id)
+    .bind(outfit_id)
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(|e| GameDataError::Internal(e.to_string()))
+}
@@ -0,0 +1,111 @@
+use crate::{model::tables::GameInfo, queries::game_info::get_game_info_query};
+use sqlx::{Pool, Sqlite, query_scalar};
+use std::{fs, path::Path};
+
+/// Builds the client cache schema in the attached `client` database and copies over client safe rows.
+/// Server only columns like `creator_user_id` are left out, as are user generated assets.
+const EXPORT_CLIENT_DATA_STATEMENTS: [&str; 6] = [
+    "CREATE TABLE client.game_info (
+        id                 INTEGER  NOT NULL PRIMARY KEY CHECK (id = 0),
+        created_at         INTEGER  NOT NULL,
+        updated_at         INTEGER  NOT NULL,
+        game_id            TEXT     NOT NULL,
+        game_version       TEXT     NOT NULL,
+        supported_client_game_ids   TEXT  NOT NULL,
+        supported_client_game_versions    TEXT  NOT NULL,
+        game_display_name  TEXT     NOT NULL
+    ) STRICT",
+    "CREATE TABLE client.content (
+        id                 INTEGER  NOT NULL PRIMARY KEY,
+        updated_at         INTEGER  NOT NULL,
+        name               TEXT     NOT NULL COLLATE NOCASE,
+        content_type       INTEGER  NOT NULL,
+        content_subtype    INTEGER  NOT NULL,
+        data               TEXT     NOT NULL,
+        asset_id_0         INTEGER,
+        asset_id_1         INTEGER,
+        asset_id_2         INTEGER,
+        asset_id_3         INTEGER,
+        asset_id_4         INTEGER,
+        is_user_generated  INTEGER  NOT NULL,
+        base_content_id    INTEGER,
+        UNIQUE(name, content_type)
+    ) STRICT",
+    "CREATE TABLE client.asset (
+        id                 INTEGER  NOT NULL PRIMARY KEY,
+        updated_at         INTEGER  NOT NULL,
+        path               TEXT     NOT NULL UNIQUE COLLATE NOCASE,
+        file_type          TEXT     NOT NULL,
+        data               ANY      NOT NULL,
+        size               INTEGER  NOT NULL,
+        hash               TEXT     NOT NULL
+    ) STRICT",
+    "INSERT INTO client.game_info (id, created_at, updated_at, game_id, game_version, supported_client_game_ids, supported_client_game_versions, game_display_name) SELECT id, created_at, updated_at, game_id, game_version, supported_client_game_ids, supported_client_game_versions, game_display_name FROM main.game_info",
+    "INSERT INTO client.content (id, updated_at, name, content_type, content_subtype, data, asset_id_0, asset_id_1, asset_id_2, asset_id_3, asset_id_4, is_user_generated, base_content_id) SELECT id, updated_at, name, content_type, content_subtype, data, asset_id_0, asset_id_1, asset_id_2, asset_id_3, asset_id_4, is_user_generated, base_content_id FROM main.content",
+    "INSERT INTO client.asset (id, updated_at, path, file_type, data, size, hash) SELECT id, updated_at, path, file_type, data, size, hash FROM main.asset WHERE is_user_generated = FALSE",
+];
+
+pub struct ClientDataExport {
+    pub game_info: GameInfo,
+    pub content_count: i64,
+    pub asset_count: i64,
+}
+
+/// Writes a new SQLite database at `path` holding the `game_info`, `content` and `asset` rows a client can cache, replacing any existing file.
+/// The copied `game_info.game_version` lets clients tell when their cache is stale.
+pub async fn export_client_data(db: &Pool<Sqlite>, path: &str) -> Result<ClientDataExport, String> {
+    let game_info = get_game_info_query(db)
+        .await
+        .ok_or("No game_info in database, run migrate first.")?;
+    for file in [
+        path.to_owned(),
+        format!("{}-wal", path),
+        format!("{}-shm", path),
+    ] {
+        if Path::new(&file).exists() {
+            fs::remove_file(&file).map_err(|e| e.to_string())?;
+        }
+    }
+    // Pool connections can't create files and an empty file is a valid empty database.
+    fs::File::create(path).map_err(|e| e.to_string())?;
+
+    // ATTACH only applies to a single connection, so everything runs on one.
+    let mut conn = db.acquire().await.map_err(|e| e.to_string())?;
+    sqlx::query("ATTACH DATABASE $1 AS client")
+        .bind(path)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| e.to_string())?;
+    let result: Result<(i64, i64), String> = async {
+        let mut tx = sqlx::Connection::begin(&mut *conn)
+            .await
+            .map_err(|e| e.to_string())?;
+        for statement in EXPORT_CLIENT_DATA_STATEMENTS {
+            sqlx::query(statement)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        let content_count = query_scalar::<_, i64>("SELECT COUNT(*) FROM client.content")
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        let asset_count = query_scalar::<_, i64>("SELECT COUNT(*) FROM client.asset")
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        tx.commit().await.map_err(|e| e.to_string())?;
+        Ok((content_count, asset_count))
+    }
+    .await;
+    sqlx::query("DETACH DATABASE client")
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| e.to_string())?;
+    let (content_count, asset_count) = result?;
+    Ok(ClientDataExport {
+        game_info,
+        content_count,
+        asset_count,
+    })
+}
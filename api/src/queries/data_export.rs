@@ -0,0 +1,354 @@
+use crate::{
+    model::{
+        fields::AssetData,
+        tables::{Asset, Content, EquipmentSlot, GameServer, World},
+    },
+    queries::{data_validate::read_data_files, game_info::get_game_info_query},
+    utils::{BASE_CLASS_CONTENT_ID, hash_asset_data},
+};
+use serde::Serialize;
+use sqlx::{Pool, Sqlite, query_as};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::Path,
+};
+use toml::{Table, Value};
+
+/// Data file that exported rows without a `source_file` are written to.
+const DEFAULT_EXPORT_FILE: &str = "exported.toml";
+/// Data file `game_info` is written to when no data file defines it yet.
+const DEFAULT_GAME_INFO_FILE: &str = "game.toml";
+
+/// Tables of a data file in the same shape `data_import` reads. `user` and `access_token` rows aren't exported and are carried over from the existing file.
+#[derive(Debug, Default, Serialize)]
+struct ExportFile {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    game_info: Option<GameInfoRow>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    asset: Vec<AssetRow>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    game_server: Vec<GameServerRow>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    world: Vec<WorldRow>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    equipment_slot: Vec<EquipmentSlotRow>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    content: Vec<ContentRow>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    user: Vec<Table>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    access_token: Vec<Table>,
+}
+
+impl ExportFile {
+    fn is_empty(&self) -> bool {
+        self.game_info.is_none()
+            && self.asset.is_empty()
+            && self.game_server.is_empty()
+            && self.world.is_empty()
+            && self.equipment_slot.is_empty()
+            && self.content.is_empty()
+            && self.user.is_empty()
+            && self.access_token.is_empty()
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct GameInfoRow {
+    game_display_name: String,
+    game_id: String,
+    game_version: String,
+    supported_client_game_ids: Vec<String>,
+    supported_client_game_versions: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct AssetRow {
+    id: i64,
+    path: String,
+    source_path: String,
+}
+
+#[derive(Debug, Serialize)]
+struct GameServerRow {
+    id: String,
+    region_code: String,
+    display_name: String,
+}
+
+#[derive(Debug, Serialize)]
+struct WorldRow {
+    id: String,
+    game_server_id: String,
+    display_name: String,
+}
+
+#[derive(Debug, Serialize)]
+struct EquipmentSlotRow {
+    id: i64,
+    name: String,
+}
+
+#[derive(Debug, Serialize)]
+struct ContentRow {
+    id: i64,
+    name: String,
+    content_type: u16,
+    content_subtype: u16,
+    #[serde(skip_serializing_if = "Table::is_empty")]
+    data: Table,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    asset_ids: Vec<i64>,
+}
+
+/// Writes `game_info`, `game_server`, `world`, `equipment_slot`, non user generated `content` except the BaseClass, and asset metadata back into the data files under `{data_dir}/content`, grouped by `source_file`.
+/// Asset data is written to the asset's `source_path` under `data_dir` when it differs from the file already there.
+/// Data files left without any rows are removed.
+pub async fn export_content(db: &Pool<Sqlite>, data_dir: &str) -> Result<(), String> {
+    let content_dir = format!("{}/content", data_dir);
+    let (files, errors) = read_data_files(&content_dir);
+    if !errors.is_empty() {
+        return Err(errors
+            .iter()
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>()
+            .join("\n"));
+    }
+
+    // Existing files keep the rows that aren't exported, their row order, and asset rows keep their `source_path`.
+    let mut exports: BTreeMap<String, ExportFile> = BTreeMap::new();
+    let mut existing: HashMap<String, Table> = HashMap::new();
+    let mut positions: HashMap<(&str, String), usize> = HashMap::new();
+    let mut source_paths = HashMap::new();
+    let mut game_info_file = None;
+    for file in &files {
+        existing.insert(
+            file.source_file.clone(),
+            file.source.parse::<Table>().map_err(|e| e.to_string())?,
+        );
+        for table in ["asset", "game_server", "world", "equipment_slot", "content"] {
+            for (position, row) in file.data.rows(table).unwrap_or_default().iter().enumerate() {
+                let id = match row.get_ref().get("id") {
+                    Some(Value::Integer(id)) => id.to_string(),
+                    Some(Value::String(id)) => id.to_owned(),
+                    _ => continue,
+                };
+                positions.insert((table, id), position);
+            }
+        }
+        let export = exports.entry(file.source_file.clone()).or_default();
+        export.user = file
+            .data
+            .user
+            .iter()
+            .map(|row| row.get_ref().clone())
+            .collect();
+        export.access_token = file
+            .data
+            .access_token
+            .iter()
+            .map(|row| row.get_ref().clone())
+            .collect();
+        for row in &file.data.asset {
+            if let (Some(id), Some(source_path)) = (
+                row.get_ref().get("id").and_then(Value::as_integer),
+                row.get_ref().get("source_path").and_then(Value::as_str),
+            ) {
+                source_paths.insert(id, source_path.to_owned());
+            }
+        }
+        if file.data.game_info.is_some() && game_info_file.is_none() {
+            game_info_file = Some(file.source_file.clone());
+        }
+    }
+
+    let game_info = get_game_info_query(db)
+        .await
+        .ok_or("No game_info in database, run migrate first.")?;
+    exports
+        .entry(game_info_file.unwrap_or(DEFAULT_GAME_INFO_FILE.to_owned()))
+        .or_default()
+        .game_info = Some(GameInfoRow {
+        game_display_name: game_info.game_display_name,
+        game_id: game_info.game_id,
+        game_version: game_info.game_version,
+        supported_client_game_ids: game_info.supported_client_game_ids.0,
+        supported_client_game_versions: game_info.supported_client_game_versions.0,
+    });
+
+    let assets =
+        query_as::<_, Asset>("SELECT * FROM asset WHERE is_user_generated = FALSE ORDER BY id")
+            .fetch_all(db)
+            .await
+            .map_err(|e| e.to_string())?;
+    for asset in assets {
+        let source_path = source_paths
+            .remove(&asset.id)
+            .unwrap_or_else(|| format!("/assets/{}", asset.path.trim_start_matches('/')));
+        write_asset_data(data_dir, &source_path, &asset)?;
+        exports
+            .entry(asset.source_file.unwrap_or(DEFAULT_EXPORT_FILE.to_owned()))
+            .or_default()
+            .asset
+            .push(AssetRow {
+                id: asset.id,
+                path: asset.path,
+                source_path,
+            });
+    }
+
+    let game_servers = query_as::<_, GameServer>("SELECT * FROM game_server ORDER BY id")
+        .fetch_all(db)
+        .await
+        .map_err(|e| e.to_string())?;
+    for game_server in game_servers {
+        exports
+            .entry(
+                game_server
+                    .source_file
+                    .unwrap_or(DEFAULT_EXPORT_FILE.to_owned()),
+            )
+            .or_default()
+            .game_server
+            .push(GameServerRow {
+                id: game_server.id,
+                region_code: game_server.region_code,
+                display_name: game_server.display_name,
+            });
+    }
+
+    let worlds = query_as::<_, World>("SELECT * FROM world ORDER BY id")
+        .fetch_all(db)
+        .await
+        .map_err(|e| e.to_string())?;
+    for world in worlds {
+        exports
+            .entry(world.source_file.unwrap_or(DEFAULT_EXPORT_FILE.to_owned()))
+            .or_default()
+            .world
+            .push(WorldRow {
+                id: world.id,
+                game_server_id: world.game_server_id,
+                display_name: world.display_name,
+            });
+    }
+
+    let equipment_slots = query_as::<_, EquipmentSlot>("SELECT * FROM equipment_slot ORDER BY id")
+        .fetch_all(db)
+        .await
+        .map_err(|e| e.to_string())?;
+    for equipment_slot in equipment_slots {
+        exports
+            .entry(
+                equipment_slot
+                    .source_file
+                    .unwrap_or(DEFAULT_EXPORT_FILE.to_owned()),
+            )
+            .or_default()
+            .equipment_slot
+            .push(EquipmentSlotRow {
+                id: equipment_slot.id,
+                name: equipment_slot.name,
+            });
+    }
+
+    // The BaseClass row is created by a migration, so it's never written to a data file.
+    let contents = query_as::<_, Content>(
+        "SELECT * FROM content WHERE is_user_generated = FALSE AND id != $1 ORDER BY id",
+    )
+    .bind(BASE_CLASS_CONTENT_ID)
+    .fetch_all(db)
+    .await
+    .map_err(|e| e.to_string())?;
+    for content in contents {
+        // The tag comes from the row's `content_type`, so it's left out of the data table.
+        let mut data = Table::try_from(&content.data.0).map_err(|e| e.to_string())?;
+        data.remove("content_type");
+        exports
+            .entry(
+                content
+                    .source_file
+                    .unwrap_or(DEFAULT_EXPORT_FILE.to_owned()),
+            )
+            .or_default()
+            .content
+            .push(ContentRow {
+                id: content.id,
+                name: content.name,
+                content_type: content.content_type.into(),
+                content_subtype: content.content_subtype.into(),
+                data,
+                asset_ids: [
+                    content.asset_id_0,
+                    content.asset_id_1,
+                    content.asset_id_2,
+                    content.asset_id_3,
+                    content.asset_id_4,
+                ]
+                .into_iter()
+                .flatten()
+                .collect(),
+            });
+    }
+
+    for (source_file, mut export) in exports {
+        let path = format!("{}/{}", content_dir, source_file);
+        if export.is_empty() {
+            if Path::new(&path).exists() {
+                fs::remove_file(&path).map_err(|e| e.to_string())?;
+                println!("  Removed empty data file: {}", path);
+            }
+            continue;
+        }
+        // Rows already in the file keep their place, new rows go at the end in id order.
+        let position =
+            |table, id: String| positions.get(&(table, id)).copied().unwrap_or(usize::MAX);
+        export
+            .asset
+            .sort_by_key(|row| position("asset", row.id.to_string()));
+        export
+            .game_server
+            .sort_by_key(|row| position("game_server", row.id.clone()));
+        export
+            .world
+            .sort_by_key(|row| position("world", row.id.clone()));
+        export
+            .equipment_slot
+            .sort_by_key(|row| position("equipment_slot", row.id.to_string()));
+        export
+            .content
+            .sort_by_key(|row| position("content", row.id.to_string()));
+
+        let text = toml::to_string(&export).map_err(|e| e.to_string())?;
+        // Files holding the same data are left alone to keep their formatting and comments.
+        if existing.get(&source_file) == Some(&text.parse::<Table>().map_err(|e| e.to_string())?) {
+            continue;
+        }
+        write_file(&path, text.as_bytes())?;
+        println!("  Exported data file: {}", path);
+    }
+    Ok(())
+}
+
+/// Writes `Asset.data` to `source_path` under `data_dir`, leaving files that already hold the same data untouched.
+fn write_asset_data(data_dir: &str, source_path: &str, asset: &Asset) -> Result<(), String> {
+    let path = format!("{}/{}", data_dir, source_path.trim().trim_matches('/'));
+    if fs::read(&path).is_ok_and(|data| hash_asset_data(&data) == asset.hash) {
+        return Ok(());
+    }
+    let data = match &asset.data {
+        AssetData::Blob(blob) => blob.as_slice(),
+        AssetData::String(string) => string.as_bytes(),
+    };
+    write_file(&path, data)?;
+    println!("  Exported asset data: {}", path);
+    Ok(())
+}
+
+fn write_file(path: &str, data: &[u8]) -> Result<(), String> {
+    if let Some(parent) = Path::new(path).parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    fs::write(path, data).map_err(|e| e.to_string())
+}
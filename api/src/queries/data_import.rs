@@ -1,90 +1,282 @@
 use crate::{
     model::{
         IdWrapper,
-        fields::AccessLevel,
-        tables::{AccessToken, GameInfo, GameServer, User, UserPassword, UserRecoveryCode, World},
+        fields::{AccessLevel, ContentData, ContentType},
+        tables::{
+            AccessToken, EquipmentSlot, GameInfo, GameServer, User, UserPassword, UserRecoveryCode,
+            World,
+        },
     },
-    queries::game_info::get_game_info_query,
+    queries::data_validate::{SourceDataFile, read_data_files},
     utils::{
-        append_secret_to_file,
+        USER_ASSET_PATH_PREFIX, append_secret_to_file,
         authentication::{
             generate_access_token, generate_password, generate_recovery_code, get_random_id,
             hash_password,
         },
-        get_magic_cookie, read_asset_file, read_dir_recursive,
+        get_magic_cookie, hash_asset_data, is_user_asset_path, read_asset_file,
     },
 };
-use sqlx::{Pool, Sqlite, query_as};
-use std::fs;
-use toml::{Table, Value, map::Map};
+use sqlx::{Pool, Sqlite, SqliteConnection, query_as, query_scalar};
+use std::collections::HashSet;
+use toml::{Value, map::Map};
 
 const NO_VALUE: Value = Value::Boolean(false);
 
+/// Imports every data file, when `prune` is set rows that were imported before but no longer appear in any data file are deleted.
 pub async fn data_import(
     db: &Pool<Sqlite>,
     set_table_keys: Option<&[&str]>,
+    prune: bool,
 ) -> Result<GameVersion, String> {
     let table_keys = set_table_keys.unwrap_or(&[
         "asset",
         "game_server",
         "world",
+        "equipment_slot",
         "content",
         "user",
         "access_token",
     ]);
     let magic_cookie = get_magic_cookie();
-    let mut data_toml_string = String::new();
-    let files = read_dir_recursive("../data/content").unwrap();
-    for file in files {
-        data_toml_string = data_toml_string + &fs::read_to_string(file.path()).unwrap();
+    let (files, errors) = read_data_files("../data/content");
+    if !errors.is_empty() {
+        return Err(errors
+            .iter()
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>()
+            .join("\n"));
+    }
+
+    let mut game_infos = files.iter().filter_map(|file| {
+        file.data
+            .game_info
+            .as_ref()
+            .map(|game_info| (file, game_info))
+    });
+    let (file, game_info) = game_infos
+        .next()
+        .ok_or("Definiton missing for: game_info.")?;
+    if let Some((duplicate_file, duplicate)) = game_infos.next() {
+        return Err(duplicate_file
+            .error(
+                duplicate.span(),
+                format!(
+                    "Duplicate [game_info] table, first defined in {}.",
+                    file.path
+                ),
+            )
+            .to_string());
     }
-    let data = data_toml_string.parse::<Table>().unwrap();
-
-    let err_text = "Definiton invalid for: game_data.";
-    let version = update_game_info(
-        db,
-        data.get("game_info")
-            .unwrap_or(&NO_VALUE)
-            .as_table()
-            .ok_or(err_text)?,
-    )
-    .await?;
-    if !version.is_new_version {
-        return Ok(version);
+    // Everything is imported in one transaction, which rolls back when dropped on error.
+    let mut tx = db.begin().await.map_err(|e| e.to_string())?;
+    let version = update_game_info(&mut tx, game_info.get_ref())
+        .await
+        .map_err(|e| file.error(game_info.span(), e).to_string())?;
+
+    // Secrets are only written once the import is committed.
+    let mut secrets = Vec::new();
+    // Rows are only upserted for a new game version, stale rows are still reported and pruned otherwise.
+    if version.is_new_version {
+        // Rows are imported table by table, and within a table in sorted file order.
+        for key in table_keys {
+            for file in &files {
+                let rows = file
+                    .data
+                    .rows(key)
+                    .ok_or(format!("Unsupported table name: '{}'.", key))?;
+                for row in rows {
+                    let source_file = &file.source_file;
+                    let row_data = row.get_ref();
+                    match *key {
+                        "asset" => {
+                            import_asset_row(&mut tx, &magic_cookie, row_data, source_file).await
+                        }
+                        "game_server" => {
+                            import_game_server_row(&mut tx, row_data, source_file).await
+                        }
+                        "world" => import_world_row(&mut tx, row_data, source_file).await,
+                        "equipment_slot" => {
+                            import_equipment_slot_row(&mut tx, row_data, source_file).await
+                        }
+                        "content" => import_content_row(&mut tx, row_data, source_file).await,
+                        "user" => {
+                            import_user_row(&mut tx, row_data, source_file, &mut secrets).await
+                        }
+                        "access_token" => {
+                            import_access_token_row(&mut tx, row_data, source_file, &mut secrets)
+                                .await
+                        }
+                        _ => return Err(format!("Unsupported table name: '{}'.", key)),
+                    }
+                    .map_err(|e| {
+                        file.error(row.span(), format!("Invalid {} row. {}", key, e))
+                            .to_string()
+                    })?;
+                }
+            }
+        }
     }
 
-    for key in table_keys {
-        let err_text = format!("Table definiton invalid for table: '{}'", key);
-        let value = data.get(*key).ok_or(&err_text)?;
-        for entry in value.as_array().ok_or(&err_text)? {
-            let row = entry.as_table().ok_or(&err_text)?;
-
-            match *key {
-                "asset" => import_asset_row(db, &magic_cookie, row)
-                    .await
-                    .map_err(|e| format!("{}. {}", &err_text, e))?,
-                "game_server" => import_game_server_row(db, row)
-                    .await
-                    .map_err(|e| format!("{}. {}", &err_text, e))?,
-                "world" => import_world_row(db, row)
-                    .await
-                    .map_err(|e| format!("{}. {}", &err_text, e))?,
-                "content" => import_content_row(db, row)
-                    .await
-                    .map_err(|e| format!("{}. {}", &err_text, e))?,
-                "user" => import_user_row(db, row)
-                    .await
-                    .map_err(|e| format!("{}. {}", &err_text, e))?,
-                "access_token" => import_access_token_row(db, row)
-                    .await
-                    .map_err(|e| format!("{}. {}", &err_text, e))?,
-                _ => return Err("Unsupported table name.".to_owned()),
-            };
+    // Assets from before hashes were stored have an empty hash, which is filled in on every import since their rows may never be upserted again.
+    hash_unhashed_assets(&mut tx).await?;
+
+    let stale_rows = find_stale_rows(&mut tx, &files, table_keys).await?;
+    if !stale_rows.is_empty() {
+        println!("\n  Rows no longer in data files:");
+        for row in &stale_rows {
+            println!("    {} {} from {}", row.table, row.id, row.source_file);
         }
+        if prune {
+            println!("\n  Pruning {} rows.", stale_rows.len());
+            prune_stale_rows(&mut tx, &stale_rows).await?;
+        } else {
+            println!("\n  Run `script migrate --prune` to remove them.\n");
+        }
+    }
+    tx.commit().await.map_err(|e| e.to_string())?;
+    for secret in secrets {
+        append_secret_to_file(secret);
     }
     Ok(version)
 }
 
+/// Tables whose imported rows can be pruned, ordered so rows are deleted before the rows they reference.
+/// Users are never pruned since deleting an account takes all of its player data with it.
+const PRUNED_TABLES: [&str; 6] = [
+    "content",
+    "access_token",
+    "world",
+    "asset",
+    "game_server",
+    "equipment_slot",
+];
+
+/// An imported row that no longer appears in any data file.
+pub struct StaleRow {
+    pub table: &'static str,
+    pub id: String,
+    pub source_file: String,
+}
+
+/// Columns that reference rows of `table` as `(table, column)` pairs. A stale row that is still referenced isn't pruned.
+fn referencing_columns(table: &str) -> Vec<(&'static str, String)> {
+    match table {
+        "content" => {
+            let mut columns = vec![
+                ("content", "base_content_id".to_owned()),
+                ("item", "item_content_id".to_owned()),
+                ("item", "extra_content_id".to_owned()),
+                ("class", "class_content_id".to_owned()),
+                ("item_collection_entry", "item_content_id".to_owned()),
+                (
+                    "companion_collection_entry",
+                    "companion_content_id".to_owned(),
+                ),
+                ("collection_entry", "content_id".to_owned()),
+            ];
+            columns.extend((0..16).map(|i| ("outfit", format!("item_content_id_{}", i))));
+            columns
+        }
+        "asset" => (0..5)
+            .map(|i| ("content", format!("asset_id_{}", i)))
+            .collect(),
+        "world" => vec![
+            ("character", "home_world_id".to_owned()),
+            ("guild", "home_world_id".to_owned()),
+        ],
+        "game_server" => vec![
+            ("world", "game_server_id".to_owned()),
+            ("access_token", "game_server_id".to_owned()),
+        ],
+        _ => Vec::new(),
+    }
+}
+
+/// Finds rows that were imported from a data file, but whose id isn't defined by any data file anymore.
+/// Rows created through the API have no `source_file` and are never stale.
+pub async fn find_stale_rows(
+    conn: &mut SqliteConnection,
+    files: &[SourceDataFile],
+    table_keys: &[&str],
+) -> Result<Vec<StaleRow>, String> {
+    let mut stale_rows = Vec::new();
+    for table in PRUNED_TABLES {
+        if !table_keys.contains(&table) {
+            continue;
+        }
+        let file_ids: HashSet<String> = files
+            .iter()
+            .filter_map(|file| file.data.rows(table))
+            .flatten()
+            .filter_map(|row| match row.get_ref().get("id") {
+                Some(Value::Integer(id)) => Some(id.to_string()),
+                Some(Value::String(id)) => Some(id.to_owned()),
+                _ => None,
+            })
+            .collect();
+        let rows = query_as::<_, (String, String)>(&format!(
+            "SELECT CAST(id AS TEXT), source_file FROM {} WHERE source_file IS NOT NULL ORDER BY id",
+            table
+        ))
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|e| e.to_string())?;
+        stale_rows.extend(
+            rows.into_iter()
+                .filter(|(id, _)| !file_ids.contains(id))
+                .map(|(id, source_file)| StaleRow {
+                    table,
+                    id,
+                    source_file,
+                }),
+        );
+    }
+    Ok(stale_rows)
+}
+
+/// Deletes stale rows table by table, refusing when any of a table's stale rows is still referenced.
+pub async fn prune_stale_rows(
+    conn: &mut SqliteConnection,
+    stale_rows: &[StaleRow],
+) -> Result<(), String> {
+    for table in PRUNED_TABLES {
+        let rows: Vec<&StaleRow> = stale_rows.iter().filter(|row| row.table == table).collect();
+        let mut blockers = Vec::new();
+        for row in &rows {
+            for (referencing_table, column) in referencing_columns(table) {
+                // Bound ids are text, integer id columns convert them before comparing.
+                let count = query_scalar::<_, i64>(&format!(
+                    "SELECT COUNT(*) FROM {} WHERE {} = $1",
+                    referencing_table, column
+                ))
+                .bind(&row.id)
+                .fetch_one(&mut *conn)
+                .await
+                .map_err(|e| e.to_string())?;
+                if count > 0 {
+                    blockers.push(format!(
+                        "Can't prune {} {}, still referenced by {} {}.{}.",
+                        table, row.id, count, referencing_table, column
+                    ));
+                }
+            }
+        }
+        if !blockers.is_empty() {
+            return Err(blockers.join("\n"));
+        }
+        for row in rows {
+            sqlx::query(&format!("DELETE FROM {} WHERE id = $1", table))
+                .bind(&row.id)
+                .execute(&mut *conn)
+                .await
+                .map_err(|e| e.to_string())?;
+            println!("  Pruned {}: {}", table, row.id);
+        }
+    }
+    Ok(())
+}
+
 pub struct GameVersion {
     pub is_new_version: bool,
     pub game_id: String,
@@ -92,7 +284,7 @@ pub struct GameVersion {
 }
 
 pub async fn update_game_info(
-    db: &Pool<Sqlite>,
+    conn: &mut SqliteConnection,
     game_info: &Map<String, Value>,
 ) -> Result<GameVersion, String> {
     let game_id = game_info
@@ -106,30 +298,32 @@ pub async fn update_game_info(
         .as_str()
         .ok_or("Missing game_version.")?;
 
-    if !game_version.starts_with("dev") {
-        if let Some(current_info) = get_game_info_query(&db).await {
-            if game_id == current_info.game_id && game_version == current_info.game_version {
-                return Ok(GameVersion {
-                    is_new_version: false,
-                    game_id: current_info.game_id,
-                    game_version: current_info.game_version,
-                });
-            }
-        }
+    let current_info = query_as::<_, GameInfo>("SELECT * FROM game_info WHERE id = 0")
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|e| e.to_string())?;
+    if !game_version.starts_with("dev")
+        && let Some(current_info) = current_info
+        && game_id == current_info.game_id
+        && game_version == current_info.game_version
+    {
+        return Ok(GameVersion {
+            is_new_version: false,
+            game_id: current_info.game_id,
+            game_version: current_info.game_version,
+        });
     }
 
     let supported_client_game_ids = game_info
         .get("supported_client_game_ids")
         .unwrap_or(&NO_VALUE)
         .as_array()
-        .map(|a| serde_json::to_string(a).ok())
-        .flatten();
+        .and_then(|a| serde_json::to_string(a).ok());
     let supported_client_game_versions = game_info
         .get("supported_client_game_versions")
         .unwrap_or(&NO_VALUE)
         .as_array()
-        .map(|a| serde_json::to_string(a).ok())
-        .flatten();
+        .and_then(|a| serde_json::to_string(a).ok());
     let new_info = query_as::<_, GameInfo>(
             "INSERT INTO game_info (id, game_id, game_version, game_display_name, supported_client_game_ids, supported_client_game_versions) VALUES (0, $1, $2, $3, $4, $5) ON CONFLICT(id) DO UPDATE SET game_id=excluded.game_id, game_version=excluded.game_version, game_display_name=excluded.game_display_name, supported_client_game_ids=excluded.supported_client_game_ids, supported_client_game_versions=excluded.supported_client_game_versions, updated_at=(unixepoch()) RETURNING *",
         )
@@ -138,10 +332,10 @@ pub async fn update_game_info(
         .bind(game_info.get("game_display_name").unwrap_or(&NO_VALUE).as_str())
         .bind(supported_client_game_ids)
         .bind(supported_client_game_versions)
-        .fetch_one(db)
+        .fetch_one(&mut *conn)
         .await
         .map_err(|e| e.to_string())?;
-    print!("  Updated GameInfo\n");
+    println!("  Updated GameInfo");
     Ok(GameVersion {
         is_new_version: true,
         game_id: new_info.game_id,
@@ -149,38 +343,51 @@ pub async fn update_game_info(
     })
 }
 
-pub async fn import_content_row(db: &Pool<Sqlite>, row: &Map<String, Value>) -> Result<(), String> {
-    let data = row
-        .get("data")
+pub async fn import_content_row(
+    conn: &mut SqliteConnection,
+    row: &Map<String, Value>,
+    source_file: &str,
+) -> Result<(), String> {
+    let content_type = row
+        .get("content_type")
         .unwrap_or(&NO_VALUE)
-        .as_table()
-        .map(|map| serde_json::to_string(map).ok())
-        .flatten();
+        .as_integer()
+        .and_then(|content_type| u16::try_from(content_type).ok())
+        .and_then(|content_type| ContentType::try_from(content_type).ok())
+        .ok_or("Missing or invalid content_type.")?;
+    let data = ContentData::from_toml(&content_type, row.get("data").and_then(Value::as_table))?;
+    let data = serde_json::to_string(&data).map_err(|e| e.to_string())?;
     let asset_ids: Vec<i64> = match row.get("asset_ids").unwrap_or(&NO_VALUE).as_array() {
-        Some(s) => s.iter().map(|id| id.as_integer()).flatten().collect(),
+        Some(s) => s.iter().filter_map(|id| id.as_integer()).collect(),
         None => Vec::new(),
     };
     let new_row = query_as::<_, IdWrapper>(
-            "INSERT INTO content (id, name, content_type, content_subtype, data, asset_id_0, asset_id_1, asset_id_2, asset_id_3, asset_id_4) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10) ON CONFLICT(id) DO UPDATE SET name=excluded.name, content_type=excluded.content_type, content_subtype=excluded.content_subtype, data=excluded.data, asset_id_0=excluded.asset_id_0, asset_id_1=excluded.asset_id_1, asset_id_2=excluded.asset_id_2, asset_id_3=excluded.asset_id_3, asset_id_4=excluded.asset_id_4, updated_at=(unixepoch()) RETURNING id",
+            "INSERT INTO content (id, name, content_type, content_subtype, data, asset_id_0, asset_id_1, asset_id_2, asset_id_3, asset_id_4, source_file) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11) ON CONFLICT(id) DO UPDATE SET name=excluded.name, content_type=excluded.content_type, content_subtype=excluded.content_subtype, data=excluded.data, asset_id_0=excluded.asset_id_0, asset_id_1=excluded.asset_id_1, asset_id_2=excluded.asset_id_2, asset_id_3=excluded.asset_id_3, asset_id_4=excluded.asset_id_4, source_file=excluded.source_file, updated_at=(unixepoch()) RETURNING id",
         )
         .bind(row.get("id").unwrap_or(&NO_VALUE).as_integer())
         .bind(row.get("name").unwrap_or(&NO_VALUE).as_str())
-        .bind(row.get("content_type").unwrap_or(&NO_VALUE).as_integer())
+        .bind(content_type)
         .bind(row.get("content_subtype").unwrap_or(&NO_VALUE).as_integer())
         .bind(data)
-        .bind(asset_ids.get(0))
+        .bind(asset_ids.first())
         .bind(asset_ids.get(1))
         .bind(asset_ids.get(2))
         .bind(asset_ids.get(3))
         .bind(asset_ids.get(4))
-        .fetch_one(db)
+        .bind(source_file)
+        .fetch_one(&mut *conn)
         .await
         .map_err(|e| e.to_string())?;
-    print!("  Imported Content: {}\n", new_row.id);
+    println!("  Imported Content: {}", new_row.id);
     Ok(())
 }
 
-pub async fn import_user_row(db: &Pool<Sqlite>, row: &Map<String, Value>) -> Result<(), String> {
+pub async fn import_user_row(
+    conn: &mut SqliteConnection,
+    row: &Map<String, Value>,
+    source_file: &str,
+    secrets: &mut Vec<String>,
+) -> Result<(), String> {
     let id = row
         .get("id")
         .unwrap_or(&NO_VALUE)
@@ -188,23 +395,24 @@ pub async fn import_user_row(db: &Pool<Sqlite>, row: &Map<String, Value>) -> Res
         .ok_or("Missing ID.")?;
     let is_id_conflict = sqlx::query("SELECT id from user WHERE id = $1")
         .bind(id)
-        .fetch_one(db)
+        .fetch_one(&mut *conn)
         .await
         .is_ok();
     // TODO poll for uniqueness
     let handle = get_random_id();
 
     let user_row = query_as::<_, User>(
-            "INSERT INTO user (id, handle, username, role) VALUES ($1, $2, $3, $4) ON CONFLICT(id) DO UPDATE SET username=excluded.username, role=excluded.role, updated_at=(unixepoch()) RETURNING *",
+            "INSERT INTO user (id, handle, username, role, source_file) VALUES ($1, $2, $3, $4, $5) ON CONFLICT(id) DO UPDATE SET username=excluded.username, role=excluded.role, source_file=excluded.source_file, updated_at=(unixepoch()) RETURNING *",
         )
         .bind(id)
         .bind(handle)
         .bind(row.get("username").unwrap_or(&NO_VALUE).as_str())
         .bind(row.get("role").unwrap_or(&NO_VALUE).as_integer())
-        .fetch_one(db)
+        .bind(source_file)
+        .fetch_one(&mut *conn)
         .await
         .map_err(|e| e.to_string())?;
-    print!("  Imported User: {}\n", user_row.id);
+    println!("  Imported User: {}", user_row.id);
 
     if row
         .get("generate_credentials")
@@ -224,22 +432,22 @@ pub async fn import_user_row(db: &Pool<Sqlite>, row: &Map<String, Value>) -> Res
             .bind(id + 1)
             .bind(id)
             .bind(password_hash)
-            .fetch_one(db)
+            .fetch_one(&mut *conn)
             .await
             .map_err(|e| e.to_string())?;
-        print!("  New UserPassword: {}\n", password_row.id);
+        println!("  New UserPassword: {}", password_row.id);
 
         query_as::<_, UserRecoveryCode>(
             "INSERT INTO user_recovery_code (id, user_id) VALUES ($1, $2) RETURNING *",
         )
         .bind(recovery_code_hash)
         .bind(id)
-        .fetch_one(db)
+        .fetch_one(&mut *conn)
         .await
         .map_err(|e| e.to_string())?;
-        print!("  New UserRecoveryCode\n");
+        println!("  New UserRecoveryCode");
 
-        append_secret_to_file(format!(
+        secrets.push(format!(
             "username:password={}:{}\nusername:recovery_code={}:{}",
             user_row.username, password, user_row.username, recovery_code
         ));
@@ -249,8 +457,10 @@ pub async fn import_user_row(db: &Pool<Sqlite>, row: &Map<String, Value>) -> Res
 }
 
 pub async fn import_access_token_row(
-    db: &Pool<Sqlite>,
+    conn: &mut SqliteConnection,
     row: &Map<String, Value>,
+    source_file: &str,
+    secrets: &mut Vec<String>,
 ) -> Result<(), String> {
     let id = row
         .get("id")
@@ -261,8 +471,7 @@ pub async fn import_access_token_row(
         .get("access_level")
         .unwrap_or(&NO_VALUE)
         .as_integer()
-        .map(|a| AccessLevel::try_from(a as u16).ok())
-        .flatten()
+        .and_then(|a| AccessLevel::try_from(a as u16).ok())
         .ok_or("Missing access_level.")?;
     let game_server_id = if access_level == AccessLevel::GameServer {
         row.get("game_server_id").unwrap_or(&NO_VALUE).as_str()
@@ -272,7 +481,7 @@ pub async fn import_access_token_row(
 
     let is_id_conflict = sqlx::query("SELECT id from access_token WHERE id = $1")
         .bind(id)
-        .fetch_one(db)
+        .fetch_one(&mut *conn)
         .await
         .is_ok();
 
@@ -280,30 +489,32 @@ pub async fn import_access_token_row(
         .ok_or("Failed to generate token.")?;
 
     let new_row = query_as::<_, AccessToken>(
-            "INSERT INTO access_token (id, access_token_hash, access_level, game_server_id, expires_at) VALUES ($1, $2, $3, $4, $5) ON CONFLICT(id) DO UPDATE SET access_level=excluded.access_level, game_server_id=excluded.game_server_id, expires_at=excluded.expires_at RETURNING *",
+            "INSERT INTO access_token (id, access_token_hash, access_level, game_server_id, expires_at, source_file) VALUES ($1, $2, $3, $4, $5, $6) ON CONFLICT(id) DO UPDATE SET access_level=excluded.access_level, game_server_id=excluded.game_server_id, expires_at=excluded.expires_at, source_file=excluded.source_file RETURNING *",
         )
         .bind(id)
         .bind(token_hash)
         .bind(access_level)
         .bind(game_server_id)
         .bind(row.get("expires_at").unwrap_or(&NO_VALUE).as_integer())
-        .fetch_one(db)
+        .bind(source_file)
+        .fetch_one(&mut *conn)
         .await
         .map_err(|e| e.to_string())?;
-    print!("  Imported AccessToken: {}\n", new_row.id);
+    println!("  Imported AccessToken: {}", new_row.id);
 
     if !is_id_conflict {
-        append_secret_to_file(format!("access_token={}", token));
+        secrets.push(format!("access_token={}", token));
     }
     Ok(())
 }
 
 pub async fn import_game_server_row(
-    db: &Pool<Sqlite>,
+    conn: &mut SqliteConnection,
     row: &Map<String, Value>,
+    source_file: &str,
 ) -> Result<(), String> {
     let new_row = query_as::<_, GameServer>(
-            "INSERT INTO game_server (id, region_code, display_name) VALUES ($1, $2, $3) ON CONFLICT(id) DO UPDATE SET region_code=excluded.region_code, display_name=excluded.display_name, updated_at=(unixepoch()) RETURNING *",
+            "INSERT INTO game_server (id, region_code, display_name, source_file) VALUES ($1, $2, $3, $4) ON CONFLICT(id) DO UPDATE SET region_code=excluded.region_code, display_name=excluded.display_name, source_file=excluded.source_file, updated_at=(unixepoch()) RETURNING *",
         )
         .bind(
             row.get("id")
@@ -313,16 +524,21 @@ pub async fn import_game_server_row(
         )
         .bind(row.get("region_code").unwrap_or(&NO_VALUE).as_str())
         .bind(row.get("display_name").unwrap_or(&NO_VALUE).as_str())
-        .fetch_one(db)
+        .bind(source_file)
+        .fetch_one(&mut *conn)
         .await
         .map_err(|e| e.to_string())?;
-    print!("  Imported GameServer: {}\n", new_row.id);
+    println!("  Imported GameServer: {}", new_row.id);
     Ok(())
 }
 
-pub async fn import_world_row(db: &Pool<Sqlite>, row: &Map<String, Value>) -> Result<(), String> {
+pub async fn import_world_row(
+    conn: &mut SqliteConnection,
+    row: &Map<String, Value>,
+    source_file: &str,
+) -> Result<(), String> {
     let new_row = query_as::<_, World>(
-            "INSERT INTO world (id, game_server_id, display_name) VALUES ($1, $2, $3) ON CONFLICT(id) DO UPDATE SET game_server_id=excluded.game_server_id, display_name=excluded.display_name, updated_at=(unixepoch()) RETURNING *",
+            "INSERT INTO world (id, game_server_id, display_name, source_file) VALUES ($1, $2, $3, $4) ON CONFLICT(id) DO UPDATE SET game_server_id=excluded.game_server_id, display_name=excluded.display_name, source_file=excluded.source_file, updated_at=(unixepoch()) RETURNING *",
         )
         .bind(
             row.get("id")
@@ -332,17 +548,42 @@ pub async fn import_world_row(db: &Pool<Sqlite>, row: &Map<String, Value>) -> Re
         )
         .bind(row.get("game_server_id").unwrap_or(&NO_VALUE).as_str())
         .bind(row.get("display_name").unwrap_or(&NO_VALUE).as_str())
-        .fetch_one(db)
+        .bind(source_file)
+        .fetch_one(&mut *conn)
         .await
         .map_err(|e| e.to_string())?;
-    print!("  Imported World: {}\n", new_row.id);
+    println!("  Imported World: {}", new_row.id);
+    Ok(())
+}
+
+pub async fn import_equipment_slot_row(
+    conn: &mut SqliteConnection,
+    row: &Map<String, Value>,
+    source_file: &str,
+) -> Result<(), String> {
+    let new_row = query_as::<_, EquipmentSlot>(
+            "INSERT INTO equipment_slot (id, name, source_file) VALUES ($1, $2, $3) ON CONFLICT(id) DO UPDATE SET name=excluded.name, source_file=excluded.source_file, updated_at=(unixepoch()) RETURNING *",
+        )
+        .bind(
+            row.get("id")
+                .unwrap_or(&NO_VALUE)
+                .as_integer()
+                .ok_or("Missing ID.")?,
+        )
+        .bind(row.get("name").unwrap_or(&NO_VALUE).as_str())
+        .bind(source_file)
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|e| e.to_string())?;
+    println!("  Imported EquipmentSlot: {}", new_row.name);
     Ok(())
 }
 
 pub async fn import_asset_row(
-    db: &Pool<Sqlite>,
+    conn: &mut SqliteConnection,
     magic_cookie: &magic::Cookie<magic::cookie::Load>,
     row: &Map<String, Value>,
+    source_file: &str,
 ) -> Result<(), String> {
     let temp_path = row
         .get("source_path")
@@ -361,20 +602,57 @@ pub async fn import_asset_row(
         .unwrap_or(&NO_VALUE)
         .as_str()
         .ok_or("Missing path.")?;
+    if is_user_asset_path(path) {
+        return Err(format!(
+            "Path can't start with '{}', it's reserved for user generated assets.",
+            USER_ASSET_PATH_PREFIX
+        ));
+    }
 
-    let (asset_data, file_size, file_type) =
-        read_asset_file(&source_path, &magic_cookie).map_err(|e| e.to_string())?;
+    let (asset_data, file_size, file_type, hash) =
+        read_asset_file(&source_path, magic_cookie).map_err(|e| e.to_string())?;
+    // Rows whose hash, path and file type are unchanged are left alone so `updated_at` only moves when the asset does.
     let new_row = query_as::<_, IdWrapper>(
-            "INSERT INTO asset (id, path, file_type, data, size) VALUES ($1, $2, $3, $4, $5) ON CONFLICT(id) DO UPDATE SET path=excluded.path, file_type=excluded.file_type, data=excluded.data, size=excluded.size, updated_at=(unixepoch()) RETURNING id",
+            "INSERT INTO asset (id, path, file_type, data, size, hash, source_file) VALUES ($1, $2, $3, $4, $5, $6, $7) ON CONFLICT(id) DO UPDATE SET path=excluded.path, file_type=excluded.file_type, data=excluded.data, size=excluded.size, hash=excluded.hash, source_file=excluded.source_file, updated_at=(unixepoch()) WHERE asset.hash != excluded.hash OR asset.path != excluded.path OR asset.file_type != excluded.file_type OR asset.source_file IS NOT excluded.source_file RETURNING id",
         )
         .bind(id)
         .bind(path)
         .bind(file_type)
         .bind(asset_data)
         .bind(file_size)
-        .fetch_one(db)
+        .bind(hash)
+        .bind(source_file)
+        .fetch_optional(&mut *conn)
         .await
         .map_err(|e| e.to_string())?;
-    print!("  Imported Asset: {}\n", new_row.id);
+    match new_row {
+        Some(new_row) => println!("  Imported Asset: {}", new_row.id),
+        None => println!("  Unchanged Asset: {}", id),
+    }
+    Ok(())
+}
+
+/// Stores the hash of every asset whose `hash` is still empty, moving `updated_at` so synced clients pick the hash up.
+pub async fn hash_unhashed_assets(conn: &mut SqliteConnection) -> Result<(), String> {
+    let ids = query_scalar::<_, i64>("SELECT id FROM asset WHERE hash = ''")
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|e| e.to_string())?;
+    for id in ids {
+        let data = query_scalar::<_, Vec<u8>>("SELECT CAST(data AS BLOB) FROM asset WHERE id = $1")
+            .bind(id)
+            .fetch_one(&mut *conn)
+            .await
+            .map_err(|e| e.to_string())?;
+        query_scalar::<_, i64>(
+            "UPDATE asset SET hash = $2, updated_at = (unixepoch()) WHERE id = $1 RETURNING id",
+        )
+        .bind(id)
+        .bind(hash_asset_data(&data))
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|e| e.to_string())?;
+        println!("  Hashed Asset: {}", id);
+    }
     Ok(())
 }
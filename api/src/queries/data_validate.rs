@@ -0,0 +1,539 @@
+use crate::{
+    model::fields::{AccessLevel, ContentData, ContentSubtype, ContentType, Role},
+    utils::{
+        BASE_CLASS_CONTENT_ID, EQUIPMENT_SLOT_IDS, USER_ASSET_PATH_PREFIX, is_user_asset_path,
+        read_dir_recursive, validate_and_format_name, validate_and_format_string_id,
+        validate_and_format_username, validate_region_code,
+    },
+};
+use serde::Deserialize;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt, fs,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+use toml::{Spanned, Table, Value};
+
+/// Tables a data file may define, everything but `game_info` is an array of tables.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DataFile {
+    pub game_info: Option<Spanned<Table>>,
+    #[serde(default)]
+    pub asset: Vec<Spanned<Table>>,
+    #[serde(default)]
+    pub game_server: Vec<Spanned<Table>>,
+    #[serde(default)]
+    pub world: Vec<Spanned<Table>>,
+    #[serde(default)]
+    pub equipment_slot: Vec<Spanned<Table>>,
+    #[serde(default)]
+    pub content: Vec<Spanned<Table>>,
+    #[serde(default)]
+    pub user: Vec<Spanned<Table>>,
+    #[serde(default)]
+    pub access_token: Vec<Spanned<Table>>,
+}
+
+impl DataFile {
+    /// Gets the rows of an array of tables by its table name.
+    pub fn rows(&self, table: &str) -> Option<&[Spanned<Table>]> {
+        match table {
+            "asset" => Some(&self.asset),
+            "game_server" => Some(&self.game_server),
+            "world" => Some(&self.world),
+            "equipment_slot" => Some(&self.equipment_slot),
+            "content" => Some(&self.content),
+            "user" => Some(&self.user),
+            "access_token" => Some(&self.access_token),
+            _ => None,
+        }
+    }
+}
+
+/// A parsed data file along with its path and source text, which is kept to turn spans into line numbers.
+/// `source_file` is the path relative to the directory the file was read from and is saved on imported rows.
+#[derive(Debug)]
+pub struct SourceDataFile {
+    pub path: String,
+    pub source_file: String,
+    pub source: String,
+    pub data: DataFile,
+}
+
+impl SourceDataFile {
+    pub fn line(&self, span: Range<usize>) -> usize {
+        self.source[..span.start.min(self.source.len())]
+            .matches('\n')
+            .count()
+            + 1
+    }
+
+    pub fn error(&self, span: Range<usize>, message: impl Into<String>) -> DataError {
+        DataError {
+            file: self.path.clone(),
+            line: self.line(span),
+            message: message.into(),
+        }
+    }
+}
+
+/// A problem found in a data file.
+#[derive(Debug)]
+pub struct DataError {
+    pub file: String,
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for DataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.file, self.line, self.message)
+    }
+}
+
+/// Parses every `.toml` file under `dir` on its own, sorted by path. Files that fail to parse are reported and left out.
+pub fn read_data_files(dir: &str) -> (Vec<SourceDataFile>, Vec<DataError>) {
+    let mut files = Vec::new();
+    let mut errors = Vec::new();
+    let mut paths: Vec<PathBuf> = match read_dir_recursive(dir) {
+        Ok(entries) => entries
+            .iter()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+            .collect(),
+        Err(e) => {
+            errors.push(DataError {
+                file: dir.to_owned(),
+                line: 0,
+                message: e.to_string(),
+            });
+            return (files, errors);
+        }
+    };
+    paths.sort();
+
+    for path in paths {
+        let source_file = path
+            .strip_prefix(dir)
+            .unwrap_or(&path)
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        let path = path.to_string_lossy().into_owned();
+        let source = match fs::read_to_string(&path) {
+            Ok(source) => source,
+            Err(e) => {
+                errors.push(DataError {
+                    file: path,
+                    line: 0,
+                    message: e.to_string(),
+                });
+                continue;
+            }
+        };
+        let mut file = SourceDataFile {
+            path,
+            source_file,
+            source,
+            data: DataFile::default(),
+        };
+        match toml::from_str::<DataFile>(&file.source) {
+            Ok(data) => file.data = data,
+            Err(e) => {
+                errors.push(file.error(e.span().unwrap_or(0..0), e.message().replace('\n', " ")));
+                continue;
+            }
+        }
+        files.push(file);
+    }
+    (files, errors)
+}
+
+/// Checks every file under `content_dir` without touching the database, returning all errors found.
+/// Asset `source_path`s are resolved relative to `data_dir`.
+pub fn validate_data(content_dir: &str, data_dir: &str) -> Vec<DataError> {
+    let (files, mut errors) = read_data_files(content_dir);
+
+    // First pass collects the ids other rows reference, reporting duplicates.
+    let mut first_seen = FirstSeen::default();
+    let mut asset_ids = HashSet::new();
+    let mut game_server_ids = HashSet::new();
+    let mut equipment_slot_ids = HashSet::new();
+    let mut game_info_count = 0;
+    for file in &files {
+        if let Some(game_info) = &file.data.game_info {
+            game_info_count += 1;
+            errors.extend(first_seen.check(file, game_info.span(), "[game_info] table".to_owned()));
+            for key in ["game_id", "game_version", "game_display_name"] {
+                if get_str(game_info.get_ref(), key).is_none() {
+                    errors.push(file.error(game_info.span(), format!("Missing {}.", key)));
+                }
+            }
+        }
+        for row in &file.data.asset {
+            if let Some(id) = get_int(row.get_ref(), "id") {
+                asset_ids.insert(id);
+                errors.extend(first_seen.check(file, row.span(), format!("asset id '{}'", id)));
+            }
+            if let Some(path) = get_str(row.get_ref(), "path") {
+                errors.extend(first_seen.check(
+                    file,
+                    row.span(),
+                    format!("asset path '{}'", path.to_lowercase()),
+                ));
+            }
+        }
+        for row in &file.data.game_server {
+            if let Some(id) = get_str(row.get_ref(), "id").and_then(validate_and_format_string_id) {
+                game_server_ids.insert(id.clone());
+                errors.extend(first_seen.check(
+                    file,
+                    row.span(),
+                    format!("game_server id '{}'", id),
+                ));
+            }
+        }
+        for row in &file.data.world {
+            if let Some(id) = get_str(row.get_ref(), "id").and_then(validate_and_format_string_id) {
+                errors.extend(first_seen.check(file, row.span(), format!("world id '{}'", id)));
+            }
+        }
+        for row in &file.data.equipment_slot {
+            if let Some(id) = get_int(row.get_ref(), "id") {
+                equipment_slot_ids.insert(id);
+                errors.extend(first_seen.check(
+                    file,
+                    row.span(),
+                    format!("equipment_slot id '{}'", id),
+                ));
+            }
+            if let Some(name) = get_str(row.get_ref(), "name") {
+                errors.extend(first_seen.check(
+                    file,
+                    row.span(),
+                    format!("equipment_slot name '{}'", name.to_lowercase()),
+                ));
+            }
+        }
+        for row in &file.data.content {
+            if let Some(id) = get_int(row.get_ref(), "id") {
+                errors.extend(first_seen.check(file, row.span(), format!("content id '{}'", id)));
+            }
+            if let (Some(name), Some(content_type)) = (
+                get_str(row.get_ref(), "name"),
+                get_int(row.get_ref(), "content_type"),
+            ) {
+                errors.extend(first_seen.check(
+                    file,
+                    row.span(),
+                    format!(
+                        "content name '{}' for content_type {}",
+                        name.to_lowercase(),
+                        content_type
+                    ),
+                ));
+            }
+        }
+        for row in &file.data.user {
+            if let Some(id) = get_int(row.get_ref(), "id") {
+                errors.extend(first_seen.check(file, row.span(), format!("user id '{}'", id)));
+            }
+        }
+        for row in &file.data.access_token {
+            if let Some(id) = get_int(row.get_ref(), "id") {
+                errors.extend(first_seen.check(
+                    file,
+                    row.span(),
+                    format!("access_token id '{}'", id),
+                ));
+            }
+        }
+    }
+    if game_info_count == 0 {
+        errors.push(DataError {
+            file: content_dir.to_owned(),
+            line: 0,
+            message: "Missing [game_info] table.".to_owned(),
+        });
+    }
+
+    // Second pass checks each row's fields and references.
+    for file in &files {
+        for row in &file.data.asset {
+            let row_errors = validate_asset_row(row.get_ref(), data_dir);
+            errors.extend(row_errors.into_iter().map(|e| file.error(row.span(), e)));
+        }
+        for row in &file.data.game_server {
+            let row_errors = validate_game_server_row(row.get_ref());
+            errors.extend(row_errors.into_iter().map(|e| file.error(row.span(), e)));
+        }
+        for row in &file.data.world {
+            let row_errors = validate_world_row(row.get_ref(), &game_server_ids);
+            errors.extend(row_errors.into_iter().map(|e| file.error(row.span(), e)));
+        }
+        for row in &file.data.equipment_slot {
+            let row_errors = validate_equipment_slot_row(row.get_ref());
+            errors.extend(row_errors.into_iter().map(|e| file.error(row.span(), e)));
+        }
+        for row in &file.data.content {
+            let row_errors = validate_content_row(row.get_ref(), &asset_ids, &equipment_slot_ids);
+            errors.extend(row_errors.into_iter().map(|e| file.error(row.span(), e)));
+        }
+        for row in &file.data.user {
+            let row_errors = validate_user_row(row.get_ref());
+            errors.extend(row_errors.into_iter().map(|e| file.error(row.span(), e)));
+        }
+        for row in &file.data.access_token {
+            let row_errors = validate_access_token_row(row.get_ref(), &game_server_ids);
+            errors.extend(row_errors.into_iter().map(|e| file.error(row.span(), e)));
+        }
+    }
+    errors.sort_by(|a, b| a.file.cmp(&b.file).then(a.line.cmp(&b.line)));
+    errors
+}
+
+/// Remembers where each key was first defined so duplicates can point back to it.
+#[derive(Default)]
+struct FirstSeen(HashMap<String, String>);
+
+impl FirstSeen {
+    fn check(
+        &mut self,
+        file: &SourceDataFile,
+        span: Range<usize>,
+        key: String,
+    ) -> Option<DataError> {
+        match self.0.get(&key) {
+            Some(first) => Some(file.error(
+                span,
+                format!("Duplicate {}, first defined at {}.", key, first),
+            )),
+            None => {
+                let location = format!("{}:{}", file.path, file.line(span));
+                self.0.insert(key, location);
+                None
+            }
+        }
+    }
+}
+
+fn get_int(row: &Table, key: &str) -> Option<i64> {
+    row.get(key).and_then(Value::as_integer)
+}
+
+fn get_str<'a>(row: &'a Table, key: &str) -> Option<&'a str> {
+    row.get(key).and_then(Value::as_str)
+}
+
+fn validate_asset_row(row: &Table, data_dir: &str) -> Vec<String> {
+    let mut errors = Vec::new();
+    if get_int(row, "id").is_none() {
+        errors.push("Asset is missing an integer id.".to_owned());
+    }
+    match get_str(row, "path") {
+        Some(path) if is_user_asset_path(path) => errors.push(format!(
+            "Asset path can't start with '{}', it's reserved for user generated assets.",
+            USER_ASSET_PATH_PREFIX
+        )),
+        Some(_) => {}
+        None => errors.push("Asset is missing path.".to_owned()),
+    }
+    match get_str(row, "source_path") {
+        Some(source_path) => {
+            let source_path = format!("{}/{}", data_dir, source_path.trim().trim_matches('/'));
+            if !Path::new(&source_path).is_file() {
+                errors.push(format!("Asset source file '{}' not found.", source_path));
+            }
+        }
+        None => errors.push("Asset is missing source_path.".to_owned()),
+    }
+    errors
+}
+
+fn validate_game_server_row(row: &Table) -> Vec<String> {
+    let mut errors = Vec::new();
+    if get_str(row, "id")
+        .and_then(validate_and_format_string_id)
+        .is_none()
+    {
+        errors.push("GameServer id must be 2 to 32 letters, digits, or underscores.".to_owned());
+    }
+    if !get_str(row, "region_code").is_some_and(validate_region_code) {
+        errors.push("GameServer region_code must be a tz database identifier.".to_owned());
+    }
+    if get_str(row, "display_name").is_none() {
+        errors.push("GameServer is missing display_name.".to_owned());
+    }
+    errors
+}
+
+fn validate_world_row(row: &Table, game_server_ids: &HashSet<String>) -> Vec<String> {
+    let mut errors = Vec::new();
+    if get_str(row, "id")
+        .and_then(validate_and_format_string_id)
+        .is_none()
+    {
+        errors.push("World id must be 2 to 32 letters, digits, or underscores.".to_owned());
+    }
+    match get_str(row, "game_server_id") {
+        Some(game_server_id) if !game_server_ids.contains(&game_server_id.to_lowercase()) => errors
+            .push(format!(
+                "World game_server_id '{}' not found.",
+                game_server_id
+            )),
+        Some(_) => {}
+        None => errors.push("World is missing game_server_id.".to_owned()),
+    }
+    if get_str(row, "display_name").is_none() {
+        errors.push("World is missing display_name.".to_owned());
+    }
+    errors
+}
+
+fn validate_equipment_slot_row(row: &Table) -> Vec<String> {
+    let mut errors = Vec::new();
+    if !get_int(row, "id").is_some_and(|id| EQUIPMENT_SLOT_IDS.contains(&id)) {
+        errors.push("EquipmentSlot id must be an integer between 0 and 15.".to_owned());
+    }
+    if get_str(row, "name")
+        .and_then(|name| validate_and_format_name(name.to_owned()))
+        .is_none()
+    {
+        errors.push(
+            "EquipmentSlot name must be 2 to 30 characters with at most 3 single spaces."
+                .to_owned(),
+        );
+    }
+    errors
+}
+
+fn validate_content_row(
+    row: &Table,
+    asset_ids: &HashSet<i64>,
+    equipment_slot_ids: &HashSet<i64>,
+) -> Vec<String> {
+    let mut errors = Vec::new();
+    match get_int(row, "id") {
+        Some(BASE_CLASS_CONTENT_ID) => errors.push(format!(
+            "Content id {} is reserved for the BaseClass.",
+            BASE_CLASS_CONTENT_ID
+        )),
+        Some(_) => {}
+        None => errors.push("Content is missing an integer id.".to_owned()),
+    }
+    if get_str(row, "name")
+        .and_then(|name| validate_and_format_name(name.to_owned()))
+        .is_none()
+    {
+        errors.push(
+            "Content name must be 2 to 30 characters with at most 3 single spaces.".to_owned(),
+        );
+    }
+    let content_type = get_int(row, "content_type")
+        .and_then(|content_type| u16::try_from(content_type).ok())
+        .and_then(|content_type| ContentType::try_from(content_type).ok());
+    let content_subtype = get_int(row, "content_subtype")
+        .and_then(|content_subtype| u16::try_from(content_subtype).ok())
+        .and_then(|content_subtype| ContentSubtype::try_from(content_subtype).ok());
+    match &content_type {
+        Some(content_type) => {
+            match ContentData::from_toml(content_type, row.get("data").and_then(Value::as_table)) {
+                Ok(ContentData::Item(item) | ContentData::UserGeneratedItem(item)) => {
+                    match (
+                        content_subtype == Some(ContentSubtype::Equipment),
+                        item.slot,
+                    ) {
+                        (true, Some(slot)) if !equipment_slot_ids.contains(&slot) => {
+                            errors.push(format!("Content equipment_slot {} not found.", slot))
+                        }
+                        (true, None) => {
+                            errors.push("Equipment content is missing slot.".to_owned())
+                        }
+                        (false, Some(_)) => {
+                            errors.push("Only Equipment content can have a slot.".to_owned())
+                        }
+                        _ => {}
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => errors.push(e),
+            }
+        }
+        None => errors.push("Content is missing a valid content_type.".to_owned()),
+    }
+    if content_subtype.is_none() {
+        errors.push("Content is missing a valid content_subtype.".to_owned());
+    }
+    if let Some(value) = row.get("asset_ids") {
+        match value.as_array() {
+            Some(ids) => {
+                if ids.len() > 5 {
+                    errors.push("Content can reference at most 5 asset_ids.".to_owned());
+                }
+                for id in ids {
+                    match id.as_integer() {
+                        Some(id) if !asset_ids.contains(&id) => {
+                            errors.push(format!("Content asset_id {} not found.", id))
+                        }
+                        Some(_) => {}
+                        None => errors.push("Content asset_ids must be integers.".to_owned()),
+                    }
+                }
+            }
+            None => errors.push("Content asset_ids must be an array.".to_owned()),
+        }
+    }
+    errors
+}
+
+fn validate_user_row(row: &Table) -> Vec<String> {
+    let mut errors = Vec::new();
+    if get_int(row, "id").is_none() {
+        errors.push("User is missing an integer id.".to_owned());
+    }
+    if get_str(row, "username")
+        .and_then(|username| validate_and_format_username(username.to_owned()))
+        .is_none()
+    {
+        errors.push("User username is invalid.".to_owned());
+    }
+    if get_int(row, "role")
+        .and_then(|role| u16::try_from(role).ok())
+        .and_then(|role| Role::try_from(role).ok())
+        .is_none()
+    {
+        errors.push("User is missing a valid role.".to_owned());
+    }
+    errors
+}
+
+fn validate_access_token_row(row: &Table, game_server_ids: &HashSet<String>) -> Vec<String> {
+    let mut errors = Vec::new();
+    if get_int(row, "id").is_none() {
+        errors.push("AccessToken is missing an integer id.".to_owned());
+    }
+    let access_level = get_int(row, "access_level")
+        .and_then(|access_level| u16::try_from(access_level).ok())
+        .and_then(|access_level| AccessLevel::try_from(access_level).ok());
+    match access_level {
+        Some(AccessLevel::GameServer) => match get_str(row, "game_server_id") {
+            Some(game_server_id) if !game_server_ids.contains(&game_server_id.to_lowercase()) => {
+                errors.push(format!(
+                    "AccessToken game_server_id '{}' not found.",
+                    game_server_id
+                ))
+            }
+            Some(_) => {}
+            None => errors.push("GameServer AccessToken is missing game_server_id.".to_owned()),
+        },
+        Some(_) => {}
+        None => errors.push("AccessToken is missing a valid access_level.".to_owned()),
+    }
+    if row.get("expires_at").is_some() && get_int(row, "expires_at").is_none() {
+        errors.push("AccessToken expires_at must be an integer unix timestamp.".to_owned());
+    }
+    errors
+}
@@ -0,0 +1,490 @@
+use crate::{
+    model::{
+        GameDataError,
+        fields::{ContentData, ContentSubtype, ItemLocation},
+        tables::{CharacterStatus, Gearset, Item},
+    },
+    queries::{
+        character::get_or_create_character_status,
+        inventory::{
+            check_container_capacity, copy_item, get_character_item, get_instance_content,
+            set_item_quantity, update_item_collection_entries,
+        },
+    },
+    utils::{BASE_SET_NAME, EQUIPMENT_SLOT_IDS, next_id, validate_and_format_name},
+};
+use sonyflake::Sonyflake;
+use sqlx::{Pool, Sqlite, SqliteConnection, query_as};
+
+/// Item ids of each equipment slot, indexed by `EquipmentSlot` id.
+pub type GearSlots = [Option<i64>; 16];
+
+/// A gearset after a change, along with the character's status, the gear it has equipped and the instances that were split or moved in or out of Equipped.
+#[derive(Debug)]
+pub struct GearsetChanges {
+    pub gearset: Gearset,
+    pub status: CharacterStatus,
+    pub equipped: GearSlots,
+    pub items: Vec<Item>,
+}
+
+/// Gear a character has equipped, the active gearset's empty slots fall through to the BASE gearset.
+pub fn equipped_slots(base: &Gearset, active: Option<&Gearset>) -> GearSlots {
+    let mut slots = base.item_id;
+    if let Some(active) = active {
+        for (slot, item_id) in slots.iter_mut().zip(active.item_id) {
+            if item_id.is_some() {
+                *slot = item_id;
+            }
+        }
+    }
+    slots
+}
+
+/// Locations an item can be equipped from, Equipped included since gearsets can share an item. Items leave their InventoryContainer when equipped.
+fn is_equippable_location(location: ItemLocation) -> bool {
+    matches!(
+        location,
+        ItemLocation::Inventory
+            | ItemLocation::InventoryContainer
+            | ItemLocation::Box
+            | ItemLocation::Equipped
+    )
+}
+
+/// Gets a gearset, only when it belongs to the character.
+async fn get_gearset(
+    conn: &mut SqliteConnection,
+    character_id: i64,
+    gearset_id: i64,
+) -> Result<Gearset, GameDataError> {
+    query_as::<_, Gearset>("SELECT * FROM gearset WHERE id = $1 AND character_id = $2")
+        .bind(gearset_id)
+        .bind(character_id)
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|e| GameDataError::Internal(e.to_string()))?
+        .ok_or(GameDataError::NotFound(format!(
+            "Gearset {} not found.",
+            gearset_id
+        )))
+}
+
+/// Gets the gear a character has equipped through its status' BASE and active gearsets.
+pub async fn get_equipped(
+    conn: &mut SqliteConnection,
+    status: &CharacterStatus,
+) -> Result<GearSlots, GameDataError> {
+    let base = get_gearset(conn, status.character_id, status.base_gearset_id).await?;
+    let active = match status.active_gearset_id {
+        Some(active_gearset_id) => {
+            Some(get_gearset(conn, status.character_id, active_gearset_id).await?)
+        }
+        None => None,
+    };
+    Ok(equipped_slots(&base, active.as_ref()))
+}
+
+async fn set_gearset_slot(
+    conn: &mut SqliteConnection,
+    gearset_id: i64,
+    slot: i64,
+    item_id: Option<i64>,
+) -> Result<Gearset, GameDataError> {
+    query_as::<_, Gearset>(&format!(
+        "UPDATE gearset SET item_id_{} = $2, updated_at = (unixepoch()) WHERE id = $1 RETURNING *",
+        slot
+    ))
+    .bind(gearset_id)
+    .bind(item_id)
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(|e| GameDataError::Internal(e.to_string()))
+}
+
+/// Moves instances that are no longer equipped to the Inventory and newly equipped instances to Equipped, returning the moved instances.
+async fn sync_equipped(
+    conn: &mut SqliteConnection,
+    sf: &Sonyflake,
+    character_id: i64,
+    before: &GearSlots,
+    after: &GearSlots,
+) -> Result<Vec<Item>, GameDataError> {
+    let mut moves = Vec::new();
+    for item_id in before.iter().flatten() {
+        if !after.contains(&Some(*item_id)) {
+            moves.push((*item_id, ItemLocation::Inventory));
+        }
+    }
+    for item_id in after.iter().flatten() {
+        if !before.contains(&Some(*item_id)) {
+            let item = get_character_item(conn, character_id, *item_id).await?;
+            if !is_equippable_location(item.location) {
+                return Err(GameDataError::FailedPrecondition(format!(
+                    "Item {} can't be equipped from {:?}.",
+                    item.id, item.location
+                )));
+            }
+            moves.push((*item_id, ItemLocation::Equipped));
+        }
+    }
+
+    let mut items = Vec::new();
+    for (item_id, location) in moves {
+        let item = query_as::<_, Item>(
+            "UPDATE item SET location = $2, container_item_id = NULL WHERE id = $1 RETURNING *",
+        )
+        .bind(item_id)
+        .bind(location)
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|e| GameDataError::Internal(e.to_string()))?;
+        update_item_collection_entries(conn, sf, &item).await?;
+        items.push(item);
+    }
+    Ok(items)
+}
+
+/// Equips a character's item in the slot its content declares, in the BASE gearset or in `gearset_id`, in one transaction.
+/// A single instance is split off stacks. When the change shows on the character the item moves to Equipped, and the item it replaced moves to the Inventory.
+pub async fn equip_item_query(
+    db: &Pool<Sqlite>,
+    sf: &Sonyflake,
+    character_id: i64,
+    item_id: i64,
+    gearset_id: Option<i64>,
+) -> Result<GearsetChanges, GameDataError> {
+    let mut tx = db
+        .begin()
+        .await
+        .map_err(|e| GameDataError::Internal(e.to_string()))?;
+    let status = get_or_create_character_status(&mut tx, sf, character_id).await?;
+    let gearset = get_gearset(
+        &mut tx,
+        character_id,
+        gearset_id.unwrap_or(status.base_gearset_id),
+    )
+    .await?;
+    let mut item = get_character_item(&mut tx, character_id, item_id).await?;
+    let (content, _) = get_instance_content(&mut tx, &item).await?;
+    let slot = match &content.data.0 {
+        ContentData::Item(data) | ContentData::UserGeneratedItem(data)
+            if content.content_subtype == ContentSubtype::Equipment =>
+        {
+            data.slot
+        }
+        _ => None,
+    }
+    .ok_or(GameDataError::InvalidArgument(format!(
+        "Item {} isn't equipment.",
+        item_id
+    )))?;
+    let is_slot = sqlx::query("SELECT id FROM equipment_slot WHERE id = $1")
+        .bind(slot)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| GameDataError::Internal(e.to_string()))?
+        .is_some();
+    if !is_slot {
+        return Err(GameDataError::FailedPrecondition(format!(
+            "Equipment slot {} isn't defined.",
+            slot
+        )));
+    }
+    if !is_equippable_location(item.location) {
+        return Err(GameDataError::FailedPrecondition(format!(
+            "Item {} can't be equipped from {:?}.",
+            item_id, item.location
+        )));
+    }
+
+    // Gearsets hold single instances, so one is split off a stack.
+    let mut split = Vec::new();
+    if item.quantity > 1 {
+        if let Some(container_item_id) = item.container_item_id {
+            check_container_capacity(&mut tx, character_id, container_item_id, 1).await?;
+        }
+        let stack = set_item_quantity(&mut tx, item.id, item.quantity - 1)
+            .await?
+            .ok_or(GameDataError::NotFound("Item not found.".to_owned()))?;
+        item = copy_item(&mut tx, sf, stack.id, 1).await?;
+        split.push(stack);
+    }
+    let before = get_equipped(&mut tx, &status).await?;
+    let gearset = set_gearset_slot(&mut tx, gearset.id, slot, Some(item.id)).await?;
+    let equipped = get_equipped(&mut tx, &status).await?;
+    let mut items = sync_equipped(&mut tx, sf, character_id, &before, &equipped).await?;
+    if !split.is_empty() && !items.iter().any(|moved| moved.id == item.id) {
+        split.push(item);
+    }
+    items.extend(split);
+    tx.commit()
+        .await
+        .map_err(|e| GameDataError::Internal(e.to_string()))?;
+    Ok(GearsetChanges {
+        gearset,
+        status,
+        equipped,
+        items,
+    })
+}
+
+/// Empties a slot of the BASE gearset or of `gearset_id` in one transaction, moving the item to the Inventory when it's no longer equipped.
+pub async fn unequip_item_query(
+    db: &Pool<Sqlite>,
+    sf: &Sonyflake,
+    character_id: i64,
+    slot: i64,
+    gearset_id: Option<i64>,
+) -> Result<GearsetChanges, GameDataError> {
+    if !EQUIPMENT_SLOT_IDS.contains(&slot) {
+        return Err(GameDataError::InvalidArgument(
+            "Equipment slot must be between 0 and 15.".to_owned(),
+        ));
+    }
+    let mut tx = db
+        .begin()
+        .await
+        .map_err(|e| GameDataError::Internal(e.to_string()))?;
+    let status = get_or_create_character_status(&mut tx, sf, character_id).await?;
+    let gearset = get_gearset(
+        &mut tx,
+        character_id,
+        gearset_id.unwrap_or(status.base_gearset_id),
+    )
+    .await?;
+    if gearset.item_id[slot as usize].is_none() {
+        return Err(GameDataError::FailedPrecondition(format!(
+            "Equipment slot {} is already empty.",
+            slot
+        )));
+    }
+    let before = get_equipped(&mut tx, &status).await?;
+    let gearset = set_gearset_slot(&mut tx, gearset.id, slot, None).await?;
+    let equipped = get_equipped(&mut tx, &status).await?;
+    let items = sync_equipped(&mut tx, sf, character_id, &before, &equipped).await?;
+    tx.commit()
+        .await
+        .map_err(|e| GameDataError::Internal(e.to_string()))?;
+    Ok(GearsetChanges {
+        gearset,
+        status,
+        equipped,
+        items,
+    })
+}
+
+/// Saves the gear a character has equipped as a named gearset, replacing the slots of a gearset that already has the name.
+pub async fn save_gearset_query(
+    db: &Pool<Sqlite>,
+    sf: &Sonyflake,
+    character_id: i64,
+    name: String,
+) -> Result<GearsetChanges, GameDataError> {
+    let name = validate_and_format_name(name).ok_or(GameDataError::InvalidArgument(
+        "Gearset name is invalid.".to_owned(),
+    ))?;
+    if name.eq_ignore_ascii_case(BASE_SET_NAME) {
+        return Err(GameDataError::InvalidArgument(format!(
+            "Gearset name {} is reserved.",
+            BASE_SET_NAME
+        )));
+    }
+    let mut tx = db
+        .begin()
+        .await
+        .map_err(|e| GameDataError::Internal(e.to_string()))?;
+    let status = get_or_create_character_status(&mut tx, sf, character_id).await?;
+    let equipped = get_equipped(&mut tx, &status).await?;
+
+    let columns: Vec<String> = EQUIPMENT_SLOT_IDS
+        .map(|i| format!("item_id_{}", i))
+        .collect();
+    let statement = format!(
+        "INSERT INTO gearset (id, character_id, name, {}) VALUES ($1, $2, $3, {}) ON CONFLICT(character_id, name) DO UPDATE SET {}, updated_at = (unixepoch()) RETURNING *",
+        columns.join(", "),
+        (4..4 + columns.len())
+            .map(|i| format!("${}", i))
+            .collect::<Vec<_>>()
+            .join(", "),
+        columns
+            .iter()
+            .map(|column| format!("{0} = excluded.{0}", column))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    let (id, _, _) = next_id(sf).map_err(GameDataError::Internal)?;
+    let mut query = query_as::<_, Gearset>(&statement)
+        .bind(id)
+        .bind(character_id)
+        .bind(name);
+    for item_id in equipped {
+        query = query.bind(item_id);
+    }
+    let gearset = query
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| GameDataError::Internal(e.to_string()))?;
+    tx.commit()
+        .await
+        .map_err(|e| GameDataError::Internal(e.to_string()))?;
+    Ok(GearsetChanges {
+        gearset,
+        status,
+        equipped,
+        items: Vec::new(),
+    })
+}
+
+/// Activates a character's gearset over the BASE gearset in one transaction, or deactivates the active gearset when `gearset_id` is None or the BASE gearset.
+/// Every item of the new equipped gear has to be held by the character somewhere it can be equipped from, see `is_equippable_location`.
+/// The gearset's linked class becomes the active class and its linked outfit is applied. An outfit applied through the previous gearset's link is removed when the new gearset links none.
+pub async fn activate_gearset_query(
+    db: &Pool<Sqlite>,
+    sf: &Sonyflake,
+    character_id: i64,
+    gearset_id: Option<i64>,
+) -> Result<GearsetChanges, GameDataError> {
+    let mut tx = db
+        .begin()
+        .await
+        .map_err(|e| GameDataError::Internal(e.to_string()))?;
+    let status = get_or_create_character_status(&mut tx, sf, character_id).await?;
+    let gearset = get_gearset(
+        &mut tx,
+        character_id,
+        gearset_id.unwrap_or(status.base_gearset_id),
+    )
+    .await?;
+    let previous = get_gearset(
+        &mut tx,
+        character_id,
+        status.active_gearset_id.unwrap_or(status.base_gearset_id),
+    )
+    .await?;
+    let active_gearset_id = (gearset.id != status.base_gearset_id).then_some(gearset.id);
+    let active_class_id = gearset.linked_class_id.unwrap_or(status.active_class_id);
+    let active_outfit_id = match gearset.linked_outfit_id {
+        Some(outfit_id) => (outfit_id != status.base_outfit_id).then_some(outfit_id),
+        None if previous.linked_outfit_id.is_some()
+            && previous.linked_outfit_id == status.active_outfit_id =>
+        {
+            None
+        }
+        None => status.active_outfit_id,
+    };
+
+    let before = get_equipped(&mut tx, &status).await?;
+    let status = query_as::<_, CharacterStatus>(
+        "UPDATE character_status SET active_gearset_id = $2, active_class_id = $3, active_outfit_id = $4, updated_at = (unixepoch()) WHERE id = $1 RETURNING *",
+    )
+    .bind(status.id)
+    .bind(active_gearset_id)
+    .bind(active_class_id)
+    .bind(active_outfit_id)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| GameDataError::Internal(e.to_string()))?;
+    let equipped = get_equipped(&mut tx, &status).await?;
+    let items = sync_equipped(&mut tx, sf, character_id, &before, &equipped).await?;
+    tx.commit()
+        .await
+        .map_err(|e| GameDataError::Internal(e.to_string()))?;
+    Ok(GearsetChanges {
+        gearset,
+        status,
+        equipped,
+        items,
+    })
+}
+
+/// Links a class and an outfit of the character to one of its gearsets, applied the next time the gearset is activated. A None link is cleared.
+pub async fn link_gearset_query(
+    db: &Pool<Sqlite>,
+    sf: &Sonyflake,
+    character_id: i64,
+    gearset_id: i64,
+    linked_class_id: Option<i64>,
+    linked_outfit_id: Option<i64>,
+) -> Result<GearsetChanges, GameDataError> {
+    let mut tx = db
+        .begin()
+        .await
+        .map_err(|e| GameDataError::Internal(e.to_string()))?;
+    let status = get_or_create_character_status(&mut tx, sf, character_id).await?;
+    let gearset = get_gearset(&mut tx, character_id, gearset_id).await?;
+    if let Some(class_id) = linked_class_id {
+        let is_class = sqlx::query("SELECT id FROM class WHERE id = $1 AND character_id = $2")
+            .bind(class_id)
+            .bind(character_id)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| GameDataError::Internal(e.to_string()))?
+            .is_some();
+        if !is_class {
+            return Err(GameDataError::NotFound(format!(
+                "Class {} not found.",
+                class_id
+            )));
+        }
+    }
+    if let Some(outfit_id) = linked_outfit_id {
+        let is_outfit = sqlx::query("SELECT id FROM outfit WHERE id = $1 AND character_id = $2")
+            .bind(outfit_id)
+            .bind(character_id)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| GameDataError::Internal(e.to_string()))?
+            .is_some();
+        if !is_outfit {
+            return Err(GameDataError::NotFound(format!(
+                "Outfit {} not found.",
+                outfit_id
+            )));
+        }
+    }
+
+    let gearset = query_as::<_, Gearset>(
+        "UPDATE gearset SET linked_class_id = $2, linked_outfit_id = $3, updated_at = (unixepoch()) WHERE id = $1 RETURNING *",
+    )
+    .bind(gearset.id)
+    .bind(linked_class_id)
+    .bind(linked_outfit_id)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| GameDataError::Internal(e.to_string()))?;
+    let equipped = get_equipped(&mut tx, &status).await?;
+    tx.commit()
+        .await
+        .map_err(|e| GameDataError::Internal(e.to_string()))?;
+    Ok(GearsetChanges {
+        gearset,
+        status,
+        equipped,
+        items: Vec::new(),
+    })
+}
+
+/// Gets every gearset of a character in id order, the BASE gearset first, along with its status and equipped gear.
+pub async fn get_gearsets_query(
+    db: &Pool<Sqlite>,
+    sf: &Sonyflake,
+    character_id: i64,
+) -> Result<(Vec<Gearset>, CharacterStatus, GearSlots), GameDataError> {
+    let mut tx = db
+        .begin()
+        .await
+        .map_err(|e| GameDataError::Internal(e.to_string()))?;
+    let status = get_or_create_character_status(&mut tx, sf, character_id).await?;
+    let gearsets =
+        query_as::<_, Gearset>("SELECT * FROM gearset WHERE character_id = $1 ORDER BY id")
+            .bind(character_id)
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(|e| GameDataError::Internal(e.to_string()))?;
+    let equipped = get_equipped(&mut tx, &status).await?;
+    tx.commit()
+        .await
+        .map_err(|e| GameDataError::Internal(e.to_string()))?;
+    Ok((gearsets, status, equipped))
+}
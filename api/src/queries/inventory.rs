@@ -0,0 +1,986 @@
+use crate::{
+    model::{
+        GameDataError,
+        fields::{
+            ContentData, ContentSubtype, ItemCollectionEntryLocation, ItemLocation, ItemQuality,
+            ItemTradability,
+        },
+        tables::{Content, Item, ItemCollectionEntry},
+    },
+    queries::asset::resolve_content,
+    utils::{EQUIPMENT_SLOT_IDS, MAX_ADDED_INSTANCES, next_id},
+};
+use sonyflake::Sonyflake;
+use sqlx::{Pool, Sqlite, SqliteConnection, query_as, query_scalar};
+
+/// Stacking rules of an item, read from its `ItemContentData`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StackRules {
+    pub stack_size: i64,
+    pub is_unique: bool,
+}
+
+impl StackRules {
+    /// Returns None when `data` isn't item data.
+    pub fn from_content_data(data: &ContentData) -> Option<StackRules> {
+        match data {
+            ContentData::Item(item) | ContentData::UserGeneratedItem(item) => Some(StackRules {
+                stack_size: item.stack_size.max(1),
+                is_unique: item.is_unique,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Largest quantity one instance can hold at `location`. Unique items always hold 1, other items have no limit in the Box.
+    pub fn max_quantity(&self, location: ItemLocation) -> i64 {
+        if self.is_unique {
+            1
+        } else if location == ItemLocation::Box {
+            i64::MAX
+        } else {
+            self.stack_size
+        }
+    }
+}
+
+/// Instances created or updated by an inventory operation, and the ids of the instances it deleted.
+#[derive(Debug, Default)]
+pub struct ItemChanges {
+    pub items: Vec<Item>,
+    pub deleted_item_ids: Vec<i64>,
+}
+
+/// How a quantity of items is added to a location.
+#[derive(Debug, Default, PartialEq)]
+pub struct StackPlan {
+    /// `(id, quantity)` added to existing instances.
+    pub merges: Vec<(i64, i64)>,
+    /// Quantities of the new instances.
+    pub new_stacks: Vec<i64>,
+}
+
+/// Plans adding `quantity` items to `location`, where `stacks` are the `(id, quantity)` of the instances they can merge with.
+/// Older instances are filled first, whatever is left is split into new instances by `split_quantity`.
+pub fn plan_add(
+    rules: StackRules,
+    location: ItemLocation,
+    stacks: &[(i64, i64)],
+    quantity: i64,
+) -> StackPlan {
+    let max_quantity = rules.max_quantity(location);
+    let mut stacks = stacks.to_vec();
+    stacks.sort();
+
+    let mut plan = StackPlan::default();
+    let mut remaining = quantity.max(0);
+    for (id, stack_quantity) in stacks {
+        if remaining == 0 {
+            break;
+        }
+        let added = (max_quantity - stack_quantity).clamp(0, remaining);
+        if added > 0 {
+            plan.merges.push((id, added));
+            remaining -= added;
+        }
+    }
+    plan.new_stacks = split_quantity(rules, location, remaining);
+    plan
+}
+
+/// Checks that adding `quantity` items to `location` creates at most `MAX_ADDED_INSTANCES` new instances, after `stacks` are filled.
+/// Runs before `plan_add` so huge quantities are rejected without planning them.
+pub fn check_added_instances(
+    rules: StackRules,
+    location: ItemLocation,
+    stacks: &[(i64, i64)],
+    quantity: i64,
+) -> Result<(), GameDataError> {
+    let max_quantity = rules.max_quantity(location);
+    let room = stacks
+        .iter()
+        .map(|(_, stack_quantity)| (max_quantity - stack_quantity).max(0))
+        .fold(0, i64::saturating_add);
+    if quantity.saturating_sub(room) > MAX_ADDED_INSTANCES.saturating_mul(max_quantity) {
+        return Err(GameDataError::InvalidArgument(format!(
+            "Quantity would create more than {} new instances.",
+            MAX_ADDED_INSTANCES
+        )));
+    }
+    Ok(())
+}
+
+/// Splits `quantity` into as few instances as `location` allows, all full except the last.
+pub fn split_quantity(rules: StackRules, location: ItemLocation, quantity: i64) -> Vec<i64> {
+    let max_quantity = rules.max_quantity(location);
+    let mut stacks = Vec::new();
+    let mut remaining = quantity.max(0);
+    while remaining > 0 {
+        let stack = remaining.min(max_quantity);
+        stacks.push(stack);
+        remaining -= stack;
+    }
+    stacks
+}
+
+/// Adds `quantity` of the item `content_id` to a character in one transaction, see `add_item`.
+pub async fn add_item_query(
+    db: &Pool<Sqlite>,
+    sf: &Sonyflake,
+    character_id: i64,
+    content_id: i64,
+    quantity: i64,
+    location: ItemLocation,
+    quality: ItemQuality,
+) -> Result<Vec<Item>, GameDataError> {
+    let mut tx = db
+        .begin()
+        .await
+        .map_err(|e| GameDataError::Internal(e.to_string()))?;
+    let is_character = sqlx::query("SELECT id FROM character WHERE id = $1")
+        .bind(character_id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| GameDataError::Internal(e.to_string()))?
+        .is_some();
+    if !is_character {
+        return Err(GameDataError::NotFound("Character not found.".to_owned()));
+    }
+    let content = get_item_content(&mut tx, content_id).await?;
+    let items = add_item(
+        &mut tx,
+        sf,
+        character_id,
+        content,
+        quantity,
+        location,
+        quality,
+    )
+    .await?;
+    tx.commit()
+        .await
+        .map_err(|e| GameDataError::Internal(e.to_string()))?;
+    Ok(items)
+}
+
+/// Gets content by id, with user generated content resolved over its base.
+pub async fn get_item_content(
+    conn: &mut SqliteConnection,
+    content_id: i64,
+) -> Result<Content, GameDataError> {
+    let content = query_as::<_, Content>("SELECT * FROM content WHERE id = $1")
+        .bind(content_id)
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|e| GameDataError::Internal(e.to_string()))?
+        .ok_or(GameDataError::NotFound("Content not found.".to_owned()))?;
+    let base = match content.base_content_id {
+        Some(base_content_id) => query_as::<_, Content>("SELECT * FROM content WHERE id = $1")
+            .bind(base_content_id)
+            .fetch_optional(&mut *conn)
+            .await
+            .map_err(|e| GameDataError::Internal(e.to_string()))?,
+        None => None,
+    };
+    resolve_content(content, base).map_err(GameDataError::FailedPrecondition)
+}
+
+/// Adds `quantity` of the resolved item `content` at `location`, merging into the character's existing instances first and creating new ones for the rest.
+/// Instances only merge when they share `location`, `quality`, `extra_content_id` and binding, have no container, crafter or `data`, and stay within `StackRules::max_quantity`.
+/// User generated items are stored as their base content with `extra_content_id` set, and `is_soulbound` items are bound to the character.
+/// Returns every created or updated instance.
+pub async fn add_item(
+    conn: &mut SqliteConnection,
+    sf: &Sonyflake,
+    character_id: i64,
+    content: Content,
+    quantity: i64,
+    location: ItemLocation,
+    quality: ItemQuality,
+) -> Result<Vec<Item>, GameDataError> {
+    if quantity <= 0 {
+        return Err(GameDataError::InvalidArgument(
+            "Quantity must be positive.".to_owned(),
+        ));
+    }
+    if matches!(
+        location,
+        ItemLocation::Equipped | ItemLocation::InventoryContainer | ItemLocation::ClassItem
+    ) {
+        return Err(GameDataError::InvalidArgument(format!(
+            "Items can't be added directly to {:?}.",
+            location
+        )));
+    }
+    let (item_content_id, extra_content_id) = match content.base_content_id {
+        Some(base_content_id) => (base_content_id, Some(content.id)),
+        None => (content.id, None),
+    };
+    let rules = StackRules::from_content_data(&content.data.0).ok_or(
+        GameDataError::InvalidArgument(format!("Content {} isn't an item.", content.id)),
+    )?;
+    let is_soulbound = matches!(
+        &content.data.0,
+        ContentData::Item(item) | ContentData::UserGeneratedItem(item) if item.is_soulbound
+    );
+    let bound_character_id = is_soulbound.then_some(character_id);
+
+    let stacks = if rules.is_unique {
+        Vec::new()
+    } else {
+        query_as::<_, (i64, i64)>(
+            "SELECT id, quantity FROM item WHERE character_id = $1 AND item_content_id = $2 AND location = $3 AND quality = $4 AND extra_content_id IS $5 AND extra_character_id_1 IS $6 AND container_item_id IS NULL AND extra_character_id_0 IS NULL AND data IS NULL ORDER BY id",
+        )
+        .bind(character_id)
+        .bind(item_content_id)
+        .bind(location)
+        .bind(quality)
+        .bind(extra_content_id)
+        .bind(bound_character_id)
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|e| GameDataError::Internal(e.to_string()))?
+    };
+
+    check_added_instances(rules, location, &stacks, quantity)?;
+    let plan = plan_add(rules, location, &stacks, quantity);
+    let mut items = Vec::new();
+    for (id, added) in plan.merges {
+        let item = query_as::<_, Item>(
+            "UPDATE item SET quantity = quantity + $2 WHERE id = $1 RETURNING *",
+        )
+        .bind(id)
+        .bind(added)
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|e| GameDataError::Internal(e.to_string()))?;
+        items.push(item);
+    }
+    for stack_quantity in plan.new_stacks {
+        let (id, _, _) = next_id(sf).map_err(GameDataError::Internal)?;
+        let item = query_as::<_, Item>(
+            "INSERT INTO item (id, character_id, item_content_id, quantity, location, quality, extra_character_id_1, extra_content_id) VALUES ($1, $2, $3, $4, $5, $6, $7, $8) RETURNING *",
+        )
+        .bind(id)
+        .bind(character_id)
+        .bind(item_content_id)
+        .bind(stack_quantity)
+        .bind(location)
+        .bind(quality)
+        .bind(bound_character_id)
+        .bind(extra_content_id)
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|e| GameDataError::Internal(e.to_string()))?;
+        items.push(item);
+    }
+    update_item_collection_entry(conn, sf, character_id, item_content_id).await?;
+    Ok(items)
+}
+
+/// Sets the quantity of an instance, deleting it when the quantity reaches 0. Returns None when it was deleted.
+/// Deleted instances are removed from every gearset slot they're in.
+pub async fn set_item_quantity(
+    conn: &mut SqliteConnection,
+    id: i64,
+    quantity: i64,
+) -> Result<Option<Item>, GameDataError> {
+    if quantity <= 0 {
+        let slots = EQUIPMENT_SLOT_IDS;
+        sqlx::query(&format!(
+            "UPDATE gearset SET {} WHERE $1 IN ({})",
+            slots
+                .clone()
+                .map(|i| format!("item_id_{0} = NULLIF(item_id_{0}, $1)", i))
+                .collect::<Vec<_>>()
+                .join(", "),
+            slots
+                .map(|i| format!("item_id_{}", i))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))
+        .bind(id)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| GameDataError::Internal(e.to_string()))?;
+        sqlx::query("DELETE FROM item WHERE id = $1")
+            .bind(id)
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| GameDataError::Internal(e.to_string()))?;
+        return Ok(None);
+    }
+    query_as::<_, Item>("UPDATE item SET quantity = $2 WHERE id = $1 RETURNING *")
+        .bind(id)
+        .bind(quantity)
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|e| GameDataError::Internal(e.to_string()))
+}
+
+/// Lowest `ItemTradability` an item needs to be moved to `location`.
+fn required_tradability(location: ItemLocation) -> Option<ItemTradability> {
+    match location {
+        ItemLocation::Dropped => Some(ItemTradability::Droppable),
+        ItemLocation::NpcMerchant => Some(ItemTradability::NpcTradable),
+        ItemLocation::Market => Some(ItemTradability::PlayerMarketable),
+        _ => None,
+    }
+}
+
+/// Gets an item instance, only when it belongs to the character.
+pub async fn get_character_item(
+    conn: &mut SqliteConnection,
+    character_id: i64,
+    item_id: i64,
+) -> Result<Item, GameDataError> {
+    query_as::<_, Item>("SELECT * FROM item WHERE id = $1 AND character_id = $2")
+        .bind(item_id)
+        .bind(character_id)
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|e| GameDataError::Internal(e.to_string()))?
+        .ok_or(GameDataError::NotFound(format!(
+            "Item {} not found.",
+            item_id
+        )))
+}
+
+/// Gets the resolved content of an item instance, user generated content included, and its stacking rules.
+pub async fn get_instance_content(
+    conn: &mut SqliteConnection,
+    item: &Item,
+) -> Result<(Content, StackRules), GameDataError> {
+    let content =
+        get_item_content(conn, item.extra_content_id.unwrap_or(item.item_content_id)).await?;
+    let rules = StackRules::from_content_data(&content.data.0).ok_or(
+        GameDataError::FailedPrecondition(format!("Content {} isn't an item.", content.id)),
+    )?;
+    Ok((content, rules))
+}
+
+/// Number of item instances inside a container.
+async fn count_contained_items(
+    conn: &mut SqliteConnection,
+    container_item_id: i64,
+) -> Result<i64, GameDataError> {
+    query_as::<_, (i64,)>("SELECT COUNT(*) FROM item WHERE container_item_id = $1")
+        .bind(container_item_id)
+        .fetch_one(&mut *conn)
+        .await
+        .map(|(count,)| count)
+        .map_err(|e| GameDataError::Internal(e.to_string()))
+}
+
+/// Checks that `added` more instances fit in a character's `InventoryContainer` item, which has to be in the Inventory.
+pub async fn check_container_capacity(
+    conn: &mut SqliteConnection,
+    character_id: i64,
+    container_item_id: i64,
+    added: i64,
+) -> Result<(), GameDataError> {
+    let container = get_character_item(conn, character_id, container_item_id).await?;
+    let (content, _) = get_instance_content(conn, &container).await?;
+    let capacity = match &content.data.0 {
+        ContentData::Item(item) | ContentData::UserGeneratedItem(item)
+            if content.content_subtype == ContentSubtype::InventoryContainer =>
+        {
+            item.capacity.unwrap_or(0)
+        }
+        _ => {
+            return Err(GameDataError::InvalidArgument(format!(
+                "Item {} isn't a container.",
+                container_item_id
+            )));
+        }
+    };
+    if container.location != ItemLocation::Inventory {
+        return Err(GameDataError::FailedPrecondition(format!(
+            "Container {} has to be in the Inventory.",
+            container_item_id
+        )));
+    }
+    if count_contained_items(conn, container_item_id).await? + added > capacity {
+        return Err(GameDataError::FailedPrecondition(format!(
+            "Container {} can only hold {} items.",
+            container_item_id, capacity
+        )));
+    }
+    Ok(())
+}
+
+/// Returns the `(id, quantity)` of the instances at `location` that `item` can merge into, oldest first.
+/// Instances with `data` or of unique items never merge.
+async fn find_stacks(
+    conn: &mut SqliteConnection,
+    item: &Item,
+    rules: StackRules,
+    location: ItemLocation,
+    container_item_id: Option<i64>,
+) -> Result<Vec<(i64, i64)>, GameDataError> {
+    if rules.is_unique || item.data.is_some() {
+        return Ok(Vec::new());
+    }
+    query_as::<_, (i64, i64)>(
+        "SELECT id, quantity FROM item WHERE id != $1 AND character_id = $2 AND item_content_id = $3 AND location = $4 AND quality = $5 AND container_item_id IS $6 AND extra_character_id_0 IS $7 AND extra_character_id_1 IS $8 AND extra_content_id IS $9 AND data IS NULL ORDER BY id",
+    )
+    .bind(item.id)
+    .bind(item.character_id)
+    .bind(item.item_content_id)
+    .bind(location)
+    .bind(item.quality)
+    .bind(container_item_id)
+    .bind(item.extra_character_id_0)
+    .bind(item.extra_character_id_1)
+    .bind(item.extra_content_id)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|e| GameDataError::Internal(e.to_string()))
+}
+
+/// Creates a new instance with every attribute of instance `id` except its quantity.
+pub async fn copy_item(
+    conn: &mut SqliteConnection,
+    sf: &Sonyflake,
+    id: i64,
+    quantity: i64,
+) -> Result<Item, GameDataError> {
+    let (new_id, _, _) = next_id(sf).map_err(GameDataError::Internal)?;
+    query_as::<_, Item>(
+        "INSERT INTO item (id, character_id, item_content_id, quantity, location, quality, container_item_id, extra_character_id_0, extra_character_id_1, data, extra_content_id) SELECT $2, character_id, item_content_id, $3, location, quality, container_item_id, extra_character_id_0, extra_character_id_1, data, extra_content_id FROM item WHERE id = $1 RETURNING *",
+    )
+    .bind(id)
+    .bind(new_id)
+    .bind(quantity)
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(|e| GameDataError::Internal(e.to_string()))
+}
+
+/// Checks that `item` can be moved to `location`.
+/// Equipped items and ClassItems are managed elsewhere, soulbound items can't be dropped or put on the Market, and the item's `tradability` has to allow the location.
+fn check_move(
+    item: &Item,
+    content: &Content,
+    location: ItemLocation,
+    container_item_id: Option<i64>,
+) -> Result<(), GameDataError> {
+    for location in [item.location, location] {
+        if matches!(location, ItemLocation::Equipped | ItemLocation::ClassItem) {
+            return Err(GameDataError::InvalidArgument(format!(
+                "Items can't be moved to or from {:?}.",
+                location
+            )));
+        }
+    }
+    if (location == ItemLocation::InventoryContainer) != container_item_id.is_some() {
+        return Err(GameDataError::InvalidArgument(
+            "Only items moved to an InventoryContainer have a container.".to_owned(),
+        ));
+    }
+    if container_item_id == Some(item.id) {
+        return Err(GameDataError::InvalidArgument(
+            "Items can't be put inside themselves.".to_owned(),
+        ));
+    }
+    if item.extra_character_id_1.is_some()
+        && matches!(location, ItemLocation::Dropped | ItemLocation::Market)
+    {
+        return Err(GameDataError::FailedPrecondition(format!(
+            "Soulbound items can't be moved to {:?}.",
+            location
+        )));
+    }
+    if let (Some(tradability), ContentData::Item(data) | ContentData::UserGeneratedItem(data)) =
+        (required_tradability(location), &content.data.0)
+        && data.tradability < tradability
+    {
+        return Err(GameDataError::FailedPrecondition(format!(
+            "{:?} items can't be moved to {:?}.",
+            data.tradability, location
+        )));
+    }
+    if content.content_subtype == ContentSubtype::InventoryContainer
+        && location == ItemLocation::InventoryContainer
+    {
+        return Err(GameDataError::FailedPrecondition(
+            "Containers can't be put in containers.".to_owned(),
+        ));
+    }
+    Ok(())
+}
+
+/// Moves a character's item instance to `location` in one transaction, see `move_item`.
+pub async fn move_item_query(
+    db: &Pool<Sqlite>,
+    sf: &Sonyflake,
+    character_id: i64,
+    item_id: i64,
+    location: ItemLocation,
+    container_item_id: Option<i64>,
+) -> Result<ItemChanges, GameDataError> {
+    let mut tx = db
+        .begin()
+        .await
+        .map_err(|e| GameDataError::Internal(e.to_string()))?;
+    let changes = move_item(
+        &mut tx,
+        sf,
+        character_id,
+        item_id,
+        location,
+        container_item_id,
+    )
+    .await?;
+    tx.commit()
+        .await
+        .map_err(|e| GameDataError::Internal(e.to_string()))?;
+    Ok(changes)
+}
+
+/// Moves a whole instance to `location`, or into the container `container_item_id` when the location is InventoryContainer.
+/// The items merge into the instances already at the destination first, older instances before newer ones, and the moved instance is deleted when nothing is left of it.
+/// Otherwise it's split into as many instances as the destination needs. Containers with items in them can't leave the Inventory.
+pub async fn move_item(
+    conn: &mut SqliteConnection,
+    sf: &Sonyflake,
+    character_id: i64,
+    item_id: i64,
+    location: ItemLocation,
+    container_item_id: Option<i64>,
+) -> Result<ItemChanges, GameDataError> {
+    let item = get_character_item(conn, character_id, item_id).await?;
+    if item.location == location && item.container_item_id == container_item_id {
+        return Err(GameDataError::FailedPrecondition(format!(
+            "Item {} is already there.",
+            item_id
+        )));
+    }
+    let (content, rules) = get_instance_content(conn, &item).await?;
+    check_move(&item, &content, location, container_item_id)?;
+    if location != ItemLocation::Inventory && count_contained_items(conn, item.id).await? > 0 {
+        return Err(GameDataError::FailedPrecondition(
+            "Containers have to be emptied before leaving the Inventory.".to_owned(),
+        ));
+    }
+
+    let stacks = find_stacks(conn, &item, rules, location, container_item_id).await?;
+    let plan = plan_add(rules, location, &stacks, item.quantity);
+    if let Some(container_item_id) = container_item_id {
+        check_container_capacity(
+            conn,
+            character_id,
+            container_item_id,
+            plan.new_stacks.len() as i64,
+        )
+        .await?;
+    }
+
+    let mut changes = ItemChanges::default();
+    for (id, added) in plan.merges {
+        let merged = query_as::<_, Item>(
+            "UPDATE item SET quantity = quantity + $2 WHERE id = $1 RETURNING *",
+        )
+        .bind(id)
+        .bind(added)
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|e| GameDataError::Internal(e.to_string()))?;
+        changes.items.push(merged);
+    }
+    let mut new_stacks = plan.new_stacks.into_iter();
+    match new_stacks.next() {
+        Some(stack_quantity) => {
+            let moved = query_as::<_, Item>(
+                "UPDATE item SET quantity = $2, location = $3, container_item_id = $4 WHERE id = $1 RETURNING *",
+            )
+            .bind(item.id)
+            .bind(stack_quantity)
+            .bind(location)
+            .bind(container_item_id)
+            .fetch_one(&mut *conn)
+            .await
+            .map_err(|e| GameDataError::Internal(e.to_string()))?;
+            changes.items.push(moved);
+        }
+        None => {
+            set_item_quantity(conn, item.id, 0).await?;
+            changes.deleted_item_ids.push(item.id);
+        }
+    }
+    for stack_quantity in new_stacks {
+        changes
+            .items
+            .push(copy_item(conn, sf, item.id, stack_quantity).await?);
+    }
+    update_item_collection_entries(conn, sf, &item).await?;
+    Ok(changes)
+}
+
+/// Splits `quantity` off a character's item instance into a new instance in the same place, in one transaction.
+pub async fn split_stack_query(
+    db: &Pool<Sqlite>,
+    sf: &Sonyflake,
+    character_id: i64,
+    item_id: i64,
+    quantity: i64,
+) -> Result<ItemChanges, GameDataError> {
+    let mut tx = db
+        .begin()
+        .await
+        .map_err(|e| GameDataError::Internal(e.to_string()))?;
+    let item = get_character_item(&mut tx, character_id, item_id).await?;
+    if quantity <= 0 || quantity >= item.quantity {
+        return Err(GameDataError::InvalidArgument(format!(
+            "Quantity must be between 1 and {}.",
+            item.quantity - 1
+        )));
+    }
+    if let Some(container_item_id) = item.container_item_id {
+        check_container_capacity(&mut tx, character_id, container_item_id, 1).await?;
+    }
+    let split = copy_item(&mut tx, sf, item.id, quantity).await?;
+    let item = set_item_quantity(&mut tx, item.id, item.quantity - quantity)
+        .await?
+        .ok_or(GameDataError::NotFound("Item not found.".to_owned()))?;
+    tx.commit()
+        .await
+        .map_err(|e| GameDataError::Internal(e.to_string()))?;
+    Ok(ItemChanges {
+        items: vec![item, split],
+        deleted_item_ids: Vec::new(),
+    })
+}
+
+/// Merges a character's `source_item_id` instance into `target_item_id` in one transaction.
+/// The items end up at the target's location. When both are already in the same place the older instance is kept instead.
+/// The kept instance is filled up to `StackRules::max_quantity`, the other one keeps what's left and is deleted when it's empty.
+pub async fn merge_stacks_query(
+    db: &Pool<Sqlite>,
+    sf: &Sonyflake,
+    character_id: i64,
+    source_item_id: i64,
+    target_item_id: i64,
+) -> Result<ItemChanges, GameDataError> {
+    if source_item_id == target_item_id {
+        return Err(GameDataError::InvalidArgument(
+            "Items can't be merged with themselves.".to_owned(),
+        ));
+    }
+    let mut tx = db
+        .begin()
+        .await
+        .map_err(|e| GameDataError::Internal(e.to_string()))?;
+    let source = get_character_item(&mut tx, character_id, source_item_id).await?;
+    let target = get_character_item(&mut tx, character_id, target_item_id).await?;
+    let (content, rules) = get_instance_content(&mut tx, &target).await?;
+    if rules.is_unique
+        || source.data.is_some()
+        || target.data.is_some()
+        || source.item_content_id != target.item_content_id
+        || source.quality != target.quality
+        || source.extra_character_id_0 != target.extra_character_id_0
+        || source.extra_character_id_1 != target.extra_character_id_1
+        || source.extra_content_id != target.extra_content_id
+    {
+        return Err(GameDataError::FailedPrecondition(
+            "Items can't be merged.".to_owned(),
+        ));
+    }
+    let same_place =
+        source.location == target.location && source.container_item_id == target.container_item_id;
+    if !same_place {
+        check_move(&source, &content, target.location, target.container_item_id)?;
+    }
+    let (kept, other) = if same_place && source.id < target.id {
+        (source, target)
+    } else {
+        (target, source)
+    };
+
+    let added = (rules.max_quantity(kept.location) - kept.quantity).clamp(0, other.quantity);
+    if added == 0 {
+        return Err(GameDataError::FailedPrecondition(format!(
+            "Item {} is already full.",
+            kept.id
+        )));
+    }
+    let mut changes = ItemChanges::default();
+    let kept = set_item_quantity(&mut tx, kept.id, kept.quantity + added)
+        .await?
+        .ok_or(GameDataError::NotFound("Item not found.".to_owned()))?;
+    changes.items.push(kept);
+    match set_item_quantity(&mut tx, other.id, other.quantity - added).await? {
+        Some(other) => changes.items.push(other),
+        None => changes.deleted_item_ids.push(other.id),
+    }
+    update_item_collection_entries(&mut tx, sf, &other).await?;
+    tx.commit()
+        .await
+        .map_err(|e| GameDataError::Internal(e.to_string()))?;
+    Ok(changes)
+}
+
+/// Discards `quantity` of a character's item instance, or the whole instance when None, in one transaction.
+/// Containers have to be emptied before being discarded.
+pub async fn discard_item_query(
+    db: &Pool<Sqlite>,
+    sf: &Sonyflake,
+    character_id: i64,
+    item_id: i64,
+    quantity: Option<i64>,
+) -> Result<ItemChanges, GameDataError> {
+    let mut tx = db
+        .begin()
+        .await
+        .map_err(|e| GameDataError::Internal(e.to_string()))?;
+    let item = get_character_item(&mut tx, character_id, item_id).await?;
+    let quantity = quantity.unwrap_or(item.quantity);
+    if quantity <= 0 || quantity > item.quantity {
+        return Err(GameDataError::InvalidArgument(format!(
+            "Quantity must be between 1 and {}.",
+            item.quantity
+        )));
+    }
+    if count_contained_items(&mut tx, item.id).await? > 0 {
+        return Err(GameDataError::FailedPrecondition(
+            "Containers have to be emptied before being discarded.".to_owned(),
+        ));
+    }
+    let mut changes = ItemChanges::default();
+    match set_item_quantity(&mut tx, item.id, item.quantity - quantity).await? {
+        Some(item) => changes.items.push(item),
+        None => changes.deleted_item_ids.push(item.id),
+    }
+    update_item_collection_entries(&mut tx, sf, &item).await?;
+    tx.commit()
+        .await
+        .map_err(|e| GameDataError::Internal(e.to_string()))?;
+    Ok(changes)
+}
+
+/// Collection entry location of an instance at `location`, None when the character doesn't hold it there.
+/// Instances bound to the character but held by someone else count as Soulbound.
+pub fn collection_location(
+    location: ItemLocation,
+    is_holder: bool,
+) -> Option<ItemCollectionEntryLocation> {
+    if !is_holder {
+        return Some(ItemCollectionEntryLocation::Soulbound);
+    }
+    match location {
+        ItemLocation::Inventory | ItemLocation::Equipped | ItemLocation::InventoryContainer => {
+            Some(ItemCollectionEntryLocation::OnCharacter)
+        }
+        ItemLocation::ClassItem => Some(ItemCollectionEntryLocation::ClassItem),
+        ItemLocation::Box => Some(ItemCollectionEntryLocation::Box),
+        ItemLocation::Other
+        | ItemLocation::Dropped
+        | ItemLocation::NpcMerchant
+        | ItemLocation::Market => None,
+    }
+}
+
+/// Recomputes a character's collection entry for `item_content_id` from the instances it holds or is bound to.
+/// The entry takes the highest priority location and the highest quality held there, and is created the first time the character holds the item.
+/// When nothing is held anymore it becomes NotTracked with Normal quality. NotTracked entries are left alone.
+pub async fn update_item_collection_entry(
+    conn: &mut SqliteConnection,
+    sf: &Sonyflake,
+    character_id: i64,
+    item_content_id: i64,
+) -> Result<(), GameDataError> {
+    let entry_location = query_scalar::<_, ItemCollectionEntryLocation>(
+        "SELECT location FROM item_collection_entry WHERE character_id = $1 AND item_content_id = $2",
+    )
+    .bind(character_id)
+    .bind(item_content_id)
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(|e| GameDataError::Internal(e.to_string()))?;
+    if entry_location == Some(ItemCollectionEntryLocation::NotTracked) {
+        return Ok(());
+    }
+
+    let instances = query_as::<_, (i64, ItemLocation, ItemQuality)>(
+        "SELECT character_id, location, quality FROM item WHERE item_content_id = $2 AND (character_id = $1 OR extra_character_id_1 = $1)",
+    )
+    .bind(character_id)
+    .bind(item_content_id)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|e| GameDataError::Internal(e.to_string()))?;
+    let held = instances
+        .into_iter()
+        .filter_map(|(holder_id, location, quality)| {
+            collection_location(location, holder_id == character_id)
+                .map(|location| (location, quality))
+        })
+        .max();
+
+    match (entry_location, held) {
+        (None, None) => {}
+        (None, Some((location, quality))) => {
+            let (id, _, _) = next_id(sf).map_err(GameDataError::Internal)?;
+            sqlx::query(
+                "INSERT INTO item_collection_entry (id, character_id, item_content_id, location, quality) VALUES ($1, $2, $3, $4, $5)",
+            )
+            .bind(id)
+            .bind(character_id)
+            .bind(item_content_id)
+            .bind(location)
+            .bind(quality)
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| GameDataError::Internal(e.to_string()))?;
+        }
+        (Some(_), held) => {
+            let (location, quality) =
+                held.unwrap_or((ItemCollectionEntryLocation::NotTracked, ItemQuality::Normal));
+            sqlx::query(
+                "UPDATE item_collection_entry SET location = $3, quality = $4 WHERE character_id = $1 AND item_content_id = $2",
+            )
+            .bind(character_id)
+            .bind(item_content_id)
+            .bind(location)
+            .bind(quality)
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| GameDataError::Internal(e.to_string()))?;
+        }
+    }
+    Ok(())
+}
+
+/// Recomputes the collection entries of an instance's holder and of the character it's bound to.
+pub async fn update_item_collection_entries(
+    conn: &mut SqliteConnection,
+    sf: &Sonyflake,
+    item: &Item,
+) -> Result<(), GameDataError> {
+    update_item_collection_entry(conn, sf, item.character_id, item.item_content_id).await?;
+    if let Some(bound_character_id) = item.extra_character_id_1
+        && bound_character_id != item.character_id
+    {
+        update_item_collection_entry(conn, sf, bound_character_id, item.item_content_id).await?;
+    }
+    Ok(())
+}
+
+/// Pages through a character's item collection in `item_content_id` order, optionally only entries at `location`.
+pub async fn get_item_collection_query(
+    db: &Pool<Sqlite>,
+    character_id: i64,
+    page_size: i32,
+    offset: i64,
+    location: Option<ItemCollectionEntryLocation>,
+) -> Result<(Vec<ItemCollectionEntry>, i64), GameDataError> {
+    let total = query_scalar::<_, i64>(
+        "SELECT COUNT(*) FROM item_collection_entry WHERE character_id = $1 AND ($2 IS NULL OR location = $2)",
+    )
+    .bind(character_id)
+    .bind(location)
+    .fetch_one(db)
+    .await
+    .map_err(|e| GameDataError::Internal(e.to_string()))?;
+    let entries = query_as::<_, ItemCollectionEntry>(
+        "SELECT * FROM item_collection_entry WHERE character_id = $1 AND ($2 IS NULL OR location = $2) ORDER BY item_content_id LIMIT $3 OFFSET $4",
+    )
+    .bind(character_id)
+    .bind(location)
+    .bind(page_size)
+    .bind(offset)
+    .fetch_all(db)
+    .await
+    .map_err(|e| GameDataError::Internal(e.to_string()))?;
+    Ok((entries, total))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn rules() -> impl Strategy<Value = StackRules> {
+        (1i64..1000, any::<bool>()).prop_map(|(stack_size, is_unique)| StackRules {
+            stack_size,
+            is_unique,
+        })
+    }
+
+    fn location() -> impl Strategy<Value = ItemLocation> {
+        prop_oneof![Just(ItemLocation::Inventory), Just(ItemLocation::Box)]
+    }
+
+    proptest! {
+        #[test]
+        fn split_keeps_quantity_within_limits(rules in rules(), location in location(), quantity in 0i64..100_000) {
+            let stacks = split_quantity(rules, location, quantity);
+            let max_quantity = rules.max_quantity(location);
+            prop_assert_eq!(stacks.iter().sum::<i64>(), quantity);
+            prop_assert!(stacks.iter().all(|stack| *stack > 0 && *stack <= max_quantity));
+            // Only the last instance can be partially filled.
+            if let Some((_, full)) = stacks.split_last() {
+                prop_assert!(full.iter().all(|stack| *stack == max_quantity));
+            }
+        }
+
+        #[test]
+        fn add_fills_older_stacks_first(
+            rules in rules(),
+            location in location(),
+            fill in prop::collection::vec(0.0f64..=1.0, 0..8),
+            quantity in 1i64..10_000,
+        ) {
+            let max_quantity = rules.max_quantity(location);
+            let stacks: Vec<(i64, i64)> = fill
+                .iter()
+                .enumerate()
+                .map(|(id, fill)| (id as i64, ((rules.stack_size as f64 * fill) as i64).clamp(1, max_quantity)))
+                .collect();
+            let plan = plan_add(rules, location, &stacks, quantity);
+
+            let mut quantities: Vec<i64> = stacks.iter().map(|(_, stack_quantity)| *stack_quantity).collect();
+            for (id, added) in &plan.merges {
+                prop_assert!(*added > 0);
+                quantities[*id as usize] += added;
+            }
+            let merged: i64 = plan.merges.iter().map(|(_, added)| added).sum();
+            prop_assert_eq!(merged + plan.new_stacks.iter().sum::<i64>(), quantity);
+            prop_assert!(quantities.iter().all(|stack_quantity| *stack_quantity <= max_quantity));
+            // Only the last stack merged into can be left with room, and new stacks are only made once every stack is full.
+            if let Some((_, filled)) = plan.merges.split_last() {
+                prop_assert!(filled.iter().all(|(id, _)| quantities[*id as usize] == max_quantity));
+            }
+            if !plan.new_stacks.is_empty() {
+                prop_assert!(quantities.iter().all(|stack_quantity| *stack_quantity == max_quantity));
+            }
+        }
+
+        #[test]
+        fn checked_adds_stay_within_instance_limit(
+            rules in rules(),
+            location in location(),
+            fill in prop::collection::vec(0.0f64..=1.0, 0..8),
+            quantity in 1i64..50_000,
+        ) {
+            let max_quantity = rules.max_quantity(location);
+            let stacks: Vec<(i64, i64)> = fill
+                .iter()
+                .enumerate()
+                .map(|(id, fill)| (id as i64, ((rules.stack_size as f64 * fill) as i64).clamp(1, max_quantity)))
+                .collect();
+            let is_allowed = check_added_instances(rules, location, &stacks, quantity).is_ok();
+            let plan = plan_add(rules, location, &stacks, quantity);
+            prop_assert_eq!(is_allowed, plan.new_stacks.len() as i64 <= MAX_ADDED_INSTANCES);
+        }
+
+        #[test]
+        fn unique_items_never_stack(stack_size in 1i64..1000, location in location(), quantity in 1i64..100) {
+            let rules = StackRules { stack_size, is_unique: true };
+            let plan = plan_add(rules, location, &[(0, 1)], quantity);
+            prop_assert!(plan.merges.is_empty());
+            prop_assert_eq!(plan.new_stacks, vec![1; quantity as usize]);
+        }
+    }
+}
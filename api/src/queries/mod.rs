@@ -1,4 +1,13 @@
+pub mod administration;
+pub mod asset;
 pub mod authentication;
 pub mod character;
+pub mod client_data;
+pub mod data_export;
 pub mod data_import;
+pub mod data_validate;
 pub mod game_info;
+pub mod gearset;
+pub mod inventory;
+pub mod outfit;
+pub mod users;
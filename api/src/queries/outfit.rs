@@ -0,0 +1,436 @@
+use crate::{
+    model::{
+        GameDataError,
+        fields::{ContentData, ContentSubtype, Customization, ItemLocation},
+        tables::{CharacterStatus, Content, Outfit},
+    },
+    queries::{
+        character::get_or_create_character_status,
+        gearset::get_equipped,
+        inventory::{
+            collection_location, get_character_item, get_instance_content, get_item_content,
+        },
+    },
+    utils::{BASE_SET_NAME, EQUIPMENT_SLOT_IDS, next_id, validate_and_format_name},
+};
+use sonyflake::Sonyflake;
+use sqlx::{Pool, Sqlite, SqliteConnection, query_as, types::Json};
+
+/// Content ids shown in each equipment slot, indexed by `EquipmentSlot` id.
+pub type AppearanceSlots = [Option<i64>; 16];
+
+/// How a character looks, the active outfit falling through to the BASE outfit and then to the equipped gear.
+#[derive(Debug)]
+pub struct ResolvedAppearance {
+    pub content_id: AppearanceSlots,
+    pub customization: Option<Json<Customization>>, // None when no outfit overrides the character's customization
+}
+
+/// An outfit after a change, along with the character's resolved appearance.
+#[derive(Debug)]
+pub struct OutfitChanges {
+    pub outfit: Outfit,
+    pub appearance: ResolvedAppearance,
+}
+
+/// Layers the active outfit over the BASE outfit over the content of the equipped gear.
+pub fn resolve_appearance(
+    gear: AppearanceSlots,
+    base: &Outfit,
+    active: Option<&Outfit>,
+) -> ResolvedAppearance {
+    let mut content_id = gear;
+    let mut customization = None;
+    for outfit in [Some(base), active].into_iter().flatten() {
+        for (slot, glamour) in content_id.iter_mut().zip(outfit.item_content_id) {
+            if glamour.is_some() {
+                *slot = glamour;
+            }
+        }
+        if outfit.customization.is_some() {
+            customization = outfit.customization.clone();
+        }
+    }
+    ResolvedAppearance {
+        content_id,
+        customization,
+    }
+}
+
+/// Gets an outfit, only when it belongs to the character.
+async fn get_outfit(
+    conn: &mut SqliteConnection,
+    character_id: i64,
+    outfit_id: i64,
+) -> Result<Outfit, GameDataError> {
+    query_as::<_, Outfit>("SELECT * FROM outfit WHERE id = $1 AND character_id = $2")
+        .bind(outfit_id)
+        .bind(character_id)
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|e| GameDataError::Internal(e.to_string()))?
+        .ok_or(GameDataError::NotFound(format!(
+            "Outfit {} not found.",
+            outfit_id
+        )))
+}
+
+/// Gets a character's appearance through its status' equipped gear and its BASE and active outfits.
+async fn get_appearance(
+    conn: &mut SqliteConnection,
+    status: &CharacterStatus,
+) -> Result<ResolvedAppearance, GameDataError> {
+    let mut gear = [None; 16];
+    for (content_id, item_id) in gear.iter_mut().zip(get_equipped(conn, status).await?) {
+        if let Some(item_id) = item_id {
+            let item = get_character_item(conn, status.character_id, item_id).await?;
+            let (content, _) = get_instance_content(conn, &item).await?;
+            *content_id = Some(content.id);
+        }
+    }
+    let base = get_outfit(conn, status.character_id, status.base_outfit_id).await?;
+    let active = match status.active_outfit_id {
+        Some(active_outfit_id) => {
+            Some(get_outfit(conn, status.character_id, active_outfit_id).await?)
+        }
+        None => None,
+    };
+    Ok(resolve_appearance(gear, &base, active.as_ref()))
+}
+
+/// Validates a new outfit name, which can't be BASE or the name of another of the character's outfits.
+async fn check_outfit_name(
+    conn: &mut SqliteConnection,
+    character_id: i64,
+    outfit_id: Option<i64>,
+    name: String,
+) -> Result<String, GameDataError> {
+    let name = validate_and_format_name(name).ok_or(GameDataError::InvalidArgument(
+        "Outfit name is invalid.".to_owned(),
+    ))?;
+    if name.eq_ignore_ascii_case(BASE_SET_NAME) {
+        return Err(GameDataError::InvalidArgument(format!(
+            "Outfit name {} is reserved.",
+            BASE_SET_NAME
+        )));
+    }
+    let is_taken =
+        sqlx::query("SELECT id FROM outfit WHERE character_id = $1 AND name = $2 AND id IS NOT $3")
+            .bind(character_id)
+            .bind(&name)
+            .bind(outfit_id)
+            .fetch_optional(&mut *conn)
+            .await
+            .map_err(|e| GameDataError::Internal(e.to_string()))?
+            .is_some();
+    if is_taken {
+        return Err(GameDataError::FailedPrecondition(format!(
+            "Outfit {} already exists.",
+            name
+        )));
+    }
+    Ok(name)
+}
+
+/// Whether a character owns some item content, through an instance it holds or is bound to, or through a collection entry.
+/// Instances are matched like `add_item` stores them, so a user generated item and its base content are owned separately.
+/// Item collection entries can't tell them apart since they are keyed by the base content, but the instances count by the same rules.
+async fn is_content_owned(
+    conn: &mut SqliteConnection,
+    character_id: i64,
+    content: &Content,
+) -> Result<bool, GameDataError> {
+    let (item_content_id, extra_content_id) = match content.base_content_id {
+        Some(base_content_id) => (base_content_id, Some(content.id)),
+        None => (content.id, None),
+    };
+    let instances = query_as::<_, (i64, ItemLocation)>(
+        "SELECT character_id, location FROM item WHERE item_content_id = $2 AND extra_content_id IS $3 AND (character_id = $1 OR extra_character_id_1 = $1)",
+    )
+    .bind(character_id)
+    .bind(item_content_id)
+    .bind(extra_content_id)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|e| GameDataError::Internal(e.to_string()))?;
+    if instances.into_iter().any(|(holder_id, location)| {
+        collection_location(location, holder_id == character_id).is_some()
+    }) {
+        return Ok(true);
+    }
+    sqlx::query("SELECT id FROM collection_entry WHERE character_id = $1 AND content_id = $2")
+        .bind(character_id)
+        .bind(content.id)
+        .fetch_optional(&mut *conn)
+        .await
+        .map(|row| row.is_some())
+        .map_err(|e| GameDataError::Internal(e.to_string()))
+}
+
+/// Creates an empty outfit for a character, its slots fall through to the BASE outfit until glamours are applied to it.
+pub async fn create_outfit_query(
+    db: &Pool<Sqlite>,
+    sf: &Sonyflake,
+    character_id: i64,
+    name: String,
+) -> Result<OutfitChanges, GameDataError> {
+    let mut tx = db
+        .begin()
+        .await
+        .map_err(|e| GameDataError::Internal(e.to_string()))?;
+    let status = get_or_create_character_status(&mut tx, sf, character_id).await?;
+    let name = check_outfit_name(&mut tx, character_id, None, name).await?;
+    let (id, _, _) = next_id(sf).map_err(GameDataError::Internal)?;
+    let outfit = query_as::<_, Outfit>(
+        "INSERT INTO outfit (id, character_id, name) VALUES ($1, $2, $3) RETURNING *",
+    )
+    .bind(id)
+    .bind(character_id)
+    .bind(name)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| GameDataError::Internal(e.to_string()))?;
+    let appearance = get_appearance(&mut tx, &status).await?;
+    tx.commit()
+        .await
+        .map_err(|e| GameDataError::Internal(e.to_string()))?;
+    Ok(OutfitChanges { outfit, appearance })
+}
+
+/// Renames one of a character's outfits, the BASE outfit keeps its name.
+pub async fn rename_outfit_query(
+    db: &Pool<Sqlite>,
+    sf: &Sonyflake,
+    character_id: i64,
+    outfit_id: i64,
+    name: String,
+) -> Result<OutfitChanges, GameDataError> {
+    let mut tx = db
+        .begin()
+        .await
+        .map_err(|e| GameDataError::Internal(e.to_string()))?;
+    let status = get_or_create_character_status(&mut tx, sf, character_id).await?;
+    let outfit = get_outfit(&mut tx, character_id, outfit_id).await?;
+    if outfit.id == status.base_outfit_id {
+        return Err(GameDataError::InvalidArgument(format!(
+            "Outfit {} can't be renamed.",
+            BASE_SET_NAME
+        )));
+    }
+    let name = check_outfit_name(&mut tx, character_id, Some(outfit.id), name).await?;
+    let outfit = query_as::<_, Outfit>(
+        "UPDATE outfit SET name = $2, updated_at = (unixepoch()) WHERE id = $1 RETURNING *",
+    )
+    .bind(outfit.id)
+    .bind(name)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| GameDataError::Internal(e.to_string()))?;
+    let appearance = get_appearance(&mut tx, &status).await?;
+    tx.commit()
+        .await
+        .map_err(|e| GameDataError::Internal(e.to_string()))?;
+    Ok(OutfitChanges { outfit, appearance })
+}
+
+/// Deletes one of a character's outfits in one transaction, deactivating it and unlinking it from gearsets first. The BASE outfit can't be deleted.
+pub async fn delete_outfit_query(
+    db: &Pool<Sqlite>,
+    sf: &Sonyflake,
+    character_id: i64,
+    outfit_id: i64,
+) -> Result<OutfitChanges, GameDataError> {
+    let mut tx = db
+        .begin()
+        .await
+        .map_err(|e| GameDataError::Internal(e.to_string()))?;
+    let mut status = get_or_create_character_status(&mut tx, sf, character_id).await?;
+    let outfit = get_outfit(&mut tx, character_id, outfit_id).await?;
+    if outfit.id == status.base_outfit_id {
+        return Err(GameDataError::InvalidArgument(format!(
+            "Outfit {} can't be deleted.",
+            BASE_SET_NAME
+        )));
+    }
+    if status.active_outfit_id == Some(outfit.id) {
+        status = query_as::<_, CharacterStatus>(
+            "UPDATE character_status SET active_outfit_id = NULL, updated_at = (unixepoch()) WHERE id = $1 RETURNING *",
+        )
+        .bind(status.id)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| GameDataError::Internal(e.to_string()))?;
+    }
+    sqlx::query(
+        "UPDATE gearset SET linked_outfit_id = NULL, updated_at = (unixepoch()) WHERE linked_outfit_id = $1",
+    )
+    .bind(outfit.id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| GameDataError::Internal(e.to_string()))?;
+    sqlx::query("DELETE FROM outfit WHERE id = $1")
+        .bind(outfit.id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| GameDataError::Internal(e.to_string()))?;
+    let appearance = get_appearance(&mut tx, &status).await?;
+    tx.commit()
+        .await
+        .map_err(|e| GameDataError::Internal(e.to_string()))?;
+    Ok(OutfitChanges { outfit, appearance })
+}
+
+/// Applies a character's outfit over the BASE outfit, or removes the active outfit when `outfit_id` is None or the BASE outfit.
+pub async fn apply_outfit_query(
+    db: &Pool<Sqlite>,
+    sf: &Sonyflake,
+    character_id: i64,
+    outfit_id: Option<i64>,
+) -> Result<OutfitChanges, GameDataError> {
+    let mut tx = db
+        .begin()
+        .await
+        .map_err(|e| GameDataError::Internal(e.to_string()))?;
+    let status = get_or_create_character_status(&mut tx, sf, character_id).await?;
+    let outfit = get_outfit(
+        &mut tx,
+        character_id,
+        outfit_id.unwrap_or(status.base_outfit_id),
+    )
+    .await?;
+    let active_outfit_id = (outfit.id != status.base_outfit_id).then_some(outfit.id);
+    let status = query_as::<_, CharacterStatus>(
+        "UPDATE character_status SET active_outfit_id = $2, updated_at = (unixepoch()) WHERE id = $1 RETURNING *",
+    )
+    .bind(status.id)
+    .bind(active_outfit_id)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| GameDataError::Internal(e.to_string()))?;
+    let appearance = get_appearance(&mut tx, &status).await?;
+    tx.commit()
+        .await
+        .map_err(|e| GameDataError::Internal(e.to_string()))?;
+    Ok(OutfitChanges { outfit, appearance })
+}
+
+/// Shows equipment content in a slot of the BASE outfit or of `outfit_id`, or empties the slot when `content_id` is None.
+/// The content has to be Equipment for that slot and owned by the character, see `is_content_owned`.
+pub async fn apply_glamour_query(
+    db: &Pool<Sqlite>,
+    sf: &Sonyflake,
+    character_id: i64,
+    slot: i64,
+    content_id: Option<i64>,
+    outfit_id: Option<i64>,
+) -> Result<OutfitChanges, GameDataError> {
+    if !EQUIPMENT_SLOT_IDS.contains(&slot) {
+        return Err(GameDataError::InvalidArgument(
+            "Equipment slot must be between 0 and 15.".to_owned(),
+        ));
+    }
+    let mut tx = db
+        .begin()
+        .await
+        .map_err(|e| GameDataError::Internal(e.to_string()))?;
+    let status = get_or_create_character_status(&mut tx, sf, character_id).await?;
+    let outfit = get_outfit(
+        &mut tx,
+        character_id,
+        outfit_id.unwrap_or(status.base_outfit_id),
+    )
+    .await?;
+    match content_id {
+        Some(content_id) => {
+            let content = get_item_content(&mut tx, content_id).await?;
+            let content_slot = match &content.data.0 {
+                ContentData::Item(data) | ContentData::UserGeneratedItem(data)
+                    if content.content_subtype == ContentSubtype::Equipment =>
+                {
+                    data.slot
+                }
+                _ => {
+                    return Err(GameDataError::InvalidArgument(format!(
+                        "Content {} isn't equipment.",
+                        content_id
+                    )));
+                }
+            };
+            if content_slot != Some(slot) {
+                return Err(GameDataError::InvalidArgument(format!(
+                    "Content {} can't be shown in equipment slot {}.",
+                    content_id, slot
+                )));
+            }
+            if !is_content_owned(&mut tx, character_id, &content).await? {
+                return Err(GameDataError::FailedPrecondition(format!(
+                    "Content {} isn't in the character's collection.",
+                    content_id
+                )));
+            }
+        }
+        None if outfit.item_content_id[slot as usize].is_none() => {
+            return Err(GameDataError::FailedPrecondition(format!(
+                "Outfit slot {} is already empty.",
+                slot
+            )));
+        }
+        None => {}
+    }
+
+    let outfit = query_as::<_, Outfit>(&format!(
+        "UPDATE outfit SET item_content_id_{} = $2, updated_at = (unixepoch()) WHERE id = $1 RETURNING *",
+        slot
+    ))
+    .bind(outfit.id)
+    .bind(content_id)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| GameDataError::Internal(e.to_string()))?;
+    let appearance = get_appearance(&mut tx, &status).await?;
+    tx.commit()
+        .await
+        .map_err(|e| GameDataError::Internal(e.to_string()))?;
+    Ok(OutfitChanges { outfit, appearance })
+}
+
+/// Gets every outfit of a character in id order, the BASE outfit first, along with its status and resolved appearance.
+pub async fn get_outfits_query(
+    db: &Pool<Sqlite>,
+    sf: &Sonyflake,
+    character_id: i64,
+) -> Result<(Vec<Outfit>, CharacterStatus, ResolvedAppearance), GameDataError> {
+    let mut tx = db
+        .begin()
+        .await
+        .map_err(|e| GameDataError::Internal(e.to_string()))?;
+    let status = get_or_create_character_status(&mut tx, sf, character_id).await?;
+    let outfits = query_as::<_, Outfit>("SELECT * FROM outfit WHERE character_id = $1 ORDER BY id")
+        .bind(character_id)
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(|e| GameDataError::Internal(e.to_string()))?;
+    let appearance = get_appearance(&mut tx, &status).await?;
+    tx.commit()
+        .await
+        .map_err(|e| GameDataError::Internal(e.to_string()))?;
+    Ok((outfits, status, appearance))
+}
+
+/// Gets a character's resolved appearance.
+pub async fn get_appearance_query(
+    db: &Pool<Sqlite>,
+    sf: &Sonyflake,
+    character_id: i64,
+) -> Result<ResolvedAppearance, GameDataError> {
+    let mut tx = db
+        .begin()
+        .await
+        .map_err(|e| GameDataError::Internal(e.to_string()))?;
+    let status = get_or_create_character_status(&mut tx, sf, character_id).await?;
+    let appearance = get_appearance(&mut tx, &status).await?;
+    tx.commit()
+        .await
+        .map_err(|e| GameDataError::Internal(e.to_string()))?;
+    Ok(appearance)
+}
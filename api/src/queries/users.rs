@@ -0,0 +1,360 @@
+use crate::{
+    model::{
+        Ref,
+        fields::Role,
+        tables::{User, UserRecoveryCode},
+    },
+    queries::authentication::{
+        DEFAULT_LIFETIME, check_user_password_query, create_user_session_query,
+        delete_user_sessions_query,
+    },
+    utils::{
+        EQUIPMENT_SLOT_IDS,
+        authentication::{generate_recovery_code, get_random_id, hash_password, validate_password},
+        next_id, validate_and_format_username,
+    },
+};
+use sonyflake::Sonyflake;
+use sqlx::{Pool, Sqlite, query_as, query_scalar};
+
+/// Statements run in order by `delete_user_query`, each is bound to the deleted user's ID.
+/// Rows that reference the user's characters are removed before the characters themselves so foreign keys stay valid.
+const DELETE_USER_STATEMENTS: [&str; 17] = [
+    "DELETE FROM character_status WHERE character_id IN (SELECT id FROM character WHERE user_id = $1)",
+    "DELETE FROM class WHERE character_id IN (SELECT id FROM character WHERE user_id = $1)",
+    "DELETE FROM gearset WHERE character_id IN (SELECT id FROM character WHERE user_id = $1)",
+    "DELETE FROM outfit WHERE character_id IN (SELECT id FROM character WHERE user_id = $1)",
+    "DELETE FROM item_collection_entry WHERE character_id IN (SELECT id FROM character WHERE user_id = $1)",
+    "DELETE FROM companion_collection_entry WHERE character_id IN (SELECT id FROM character WHERE user_id = $1)",
+    "DELETE FROM collection_entry WHERE character_id IN (SELECT id FROM character WHERE user_id = $1)",
+    "DELETE FROM item WHERE character_id IN (SELECT id FROM character WHERE user_id = $1)",
+    "UPDATE item SET extra_character_id_0 = NULL WHERE extra_character_id_0 IN (SELECT id FROM character WHERE user_id = $1)",
+    "UPDATE item SET extra_character_id_1 = NULL WHERE extra_character_id_1 IN (SELECT id FROM character WHERE user_id = $1)",
+    "DELETE FROM friendship WHERE character_id_0 IN (SELECT id FROM character WHERE user_id = $1) OR character_id_1 IN (SELECT id FROM character WHERE user_id = $1)",
+    "DELETE FROM guild_membership WHERE character_id IN (SELECT id FROM character WHERE user_id = $1)",
+    "DELETE FROM game_options WHERE user_id = $1 OR character_id IN (SELECT id FROM character WHERE user_id = $1)",
+    "DELETE FROM character WHERE user_id = $1",
+    "DELETE FROM user_session WHERE user_id = $1",
+    "DELETE FROM user_password WHERE user_id = $1",
+    "DELETE FROM user_recovery_code WHERE user_id = $1",
+];
+
+/// Statements run by `delete_user_query` after `DELETE_USER_STATEMENTS`, each is bound to the deleted user's ID.
+/// The user's user generated content and assets are deleted, after clearing the references other users' rows still have to them.
+/// Other characters' instances of the user's user generated items fall back to their base content.
+fn delete_user_generated_statements() -> Vec<String> {
+    let content_ids = "SELECT id FROM content WHERE creator_user_id = $1";
+    let asset_ids = "SELECT id FROM asset WHERE creator_user_id = $1";
+    let mut statements = vec![
+        format!(
+            "UPDATE item SET extra_content_id = NULL WHERE extra_content_id IN ({})",
+            content_ids
+        ),
+        format!(
+            "DELETE FROM collection_entry WHERE content_id IN ({})",
+            content_ids
+        ),
+        format!(
+            "DELETE FROM companion_collection_entry WHERE companion_content_id IN ({})",
+            content_ids
+        ),
+    ];
+    statements.extend(EQUIPMENT_SLOT_IDS.map(|i| {
+        format!(
+            "UPDATE outfit SET item_content_id_{0} = NULL WHERE item_content_id_{0} IN ({1})",
+            i, content_ids
+        )
+    }));
+    statements.push("DELETE FROM content WHERE creator_user_id = $1".to_owned());
+    statements.extend((0..5).map(|i| {
+        format!(
+            "UPDATE content SET asset_id_{0} = NULL WHERE asset_id_{0} IN ({1})",
+            i, asset_ids
+        )
+    }));
+    statements.push("DELETE FROM asset WHERE creator_user_id = $1".to_owned());
+    statements
+}
+
+/// Creates a new user with a password and a permanent recovery code, then logs them in.
+/// Returns the new session token and the recovery code, neither can be retrieved again after this.
+pub async fn sign_up_query(
+    db: &Pool<Sqlite>,
+    sf: &Sonyflake,
+    username: String,
+    password: &str,
+) -> Result<(String, String, User), String> {
+    let username = validate_and_format_username(username).ok_or("Username is invalid.")?;
+    validate_password(password)?;
+    if is_username_taken_query(db, &username, None).await? {
+        return Err("Username is already taken.".to_owned());
+    }
+
+    let password_hash = hash_password(password).ok_or("Failed to hash password.")?;
+    let (recovery_code, recovery_code_hash) =
+        generate_recovery_code().ok_or("Failed to generate recovery code.")?;
+    let (id, created_at, _) = next_id(sf)?;
+    let (password_id, _, _) = next_id(sf)?;
+    // TODO poll for uniqueness
+    let handle = get_random_id();
+
+    let mut tx = db.begin().await.map_err(|e| e.to_string())?;
+    let user = query_as::<_, User>(
+        "INSERT INTO user (id, handle, updated_at, username) VALUES ($1, $2, $3, $4) RETURNING *",
+    )
+    .bind(id)
+    .bind(handle)
+    .bind(created_at)
+    .bind(username)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| e.to_string())?;
+    sqlx::query("INSERT INTO user_password (id, user_id, password_hash) VALUES ($1, $2, $3)")
+        .bind(password_id)
+        .bind(user.id)
+        .bind(password_hash)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+    sqlx::query("INSERT INTO user_recovery_code (id, user_id) VALUES ($1, $2)")
+        .bind(recovery_code_hash)
+        .bind(user.id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+    tx.commit().await.map_err(|e| e.to_string())?;
+
+    let session_token = create_user_session_query(db, user.id, DEFAULT_LIFETIME)
+        .await
+        .map_err(|_e| "Failed to create session.")?;
+    Ok((session_token, recovery_code, user))
+}
+
+pub async fn is_username_taken_query(
+    db: &Pool<Sqlite>,
+    username: &str,
+    exclude_user_id: Option<i64>,
+) -> Result<bool, String> {
+    let row = sqlx::query("SELECT id FROM user WHERE username = $1 AND id IS NOT $2")
+        .bind(username)
+        .bind(exclude_user_id)
+        .fetch_optional(db)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(row.is_some())
+}
+
+pub async fn update_username_query(
+    db: &Pool<Sqlite>,
+    user_id: i64,
+    username: String,
+) -> Result<User, String> {
+    let username = validate_and_format_username(username).ok_or("Username is invalid.")?;
+    if is_username_taken_query(db, &username, Some(user_id)).await? {
+        return Err("Username is already taken.".to_owned());
+    }
+    let user = query_as::<_, User>(
+        "UPDATE user SET username = $1, updated_at = (unixepoch()) WHERE id = $2 RETURNING *",
+    )
+    .bind(username)
+    .bind(user_id)
+    .fetch_one(db)
+    .await
+    .map_err(|e| e.to_string())?;
+    Ok(user)
+}
+
+/// Replaces a user's password hash, the caller is responsible for verifying the user's identity first.
+pub async fn set_user_password_query(
+    db: &Pool<Sqlite>,
+    sf: &Sonyflake,
+    user_id: i64,
+    new_password: &str,
+) -> Result<(), String> {
+    validate_password(new_password)?;
+    let password_hash = hash_password(new_password).ok_or("Failed to hash password.")?;
+    let (password_id, _, _) = next_id(sf)?;
+
+    let mut tx = db.begin().await.map_err(|e| e.to_string())?;
+    sqlx::query(
+        "INSERT INTO user_password (id, user_id, password_hash) VALUES ($1, $2, $3) ON CONFLICT(user_id) DO UPDATE SET password_hash=excluded.password_hash",
+    )
+    .bind(password_id)
+    .bind(user_id)
+    .bind(password_hash)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| e.to_string())?;
+    sqlx::query("UPDATE user SET updated_at = (unixepoch()) WHERE id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+    tx.commit().await.map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// If `keep_session_id` is provided every other session of the user is revoked.
+pub async fn update_password_query(
+    db: &Pool<Sqlite>,
+    sf: &Sonyflake,
+    user_id: i64,
+    password: &str,
+    new_password: &str,
+    keep_session_id: Option<&str>,
+) -> Result<(), String> {
+    check_user_password_query(db, user_id, password)
+        .await
+        .map_err(|_e| "Invalid password.")?;
+    set_user_password_query(db, sf, user_id, new_password).await?;
+    if let Some(keep_session_id) = keep_session_id {
+        delete_user_sessions_query(db, user_id, Some(keep_session_id)).await?;
+    }
+    Ok(())
+}
+
+/// Permanently deletes a user along with their characters, user generated content and assets, sessions, password, and recovery code in a single transaction.
+pub async fn delete_user_query(db: &Pool<Sqlite>, user_id: i64) -> Result<(), String> {
+    let mut tx = db.begin().await.map_err(|e| e.to_string())?;
+    let statements = DELETE_USER_STATEMENTS
+        .iter()
+        .map(|statement| statement.to_string())
+        .chain(delete_user_generated_statements())
+        .chain(["DELETE FROM user WHERE id = $1".to_owned()]);
+    for statement in statements {
+        sqlx::query(&statement)
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+    tx.commit().await.map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Consumes a recovery code and replaces the user's password, revoking every existing session.
+/// A fresh permanent recovery code is issued in place of the consumed one, this applies to temporary codes as well so they can only ever be used once.
+/// Returns a new session token and the new recovery code.
+pub async fn recover_user_query(
+    db: &Pool<Sqlite>,
+    sf: &Sonyflake,
+    recovery_code: &UserRecoveryCode,
+    new_password: &str,
+) -> Result<(String, String), String> {
+    validate_password(new_password)?;
+    let password_hash = hash_password(new_password).ok_or("Failed to hash password.")?;
+    let (new_recovery_code, new_recovery_code_hash) =
+        generate_recovery_code().ok_or("Failed to generate recovery code.")?;
+    let (password_id, _, _) = next_id(sf)?;
+
+    let mut tx = db.begin().await.map_err(|e| e.to_string())?;
+    let consumed = sqlx::query("DELETE FROM user_recovery_code WHERE id = $1 AND user_id = $2")
+        .bind(&recovery_code.id)
+        .bind(recovery_code.user_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+    if consumed.rows_affected() != 1 {
+        return Err("Recovery code has already been used.".to_owned());
+    }
+    sqlx::query(
+        "INSERT INTO user_password (id, user_id, password_hash) VALUES ($1, $2, $3) ON CONFLICT(user_id) DO UPDATE SET password_hash=excluded.password_hash",
+    )
+    .bind(password_id)
+    .bind(recovery_code.user_id)
+    .bind(password_hash)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| e.to_string())?;
+    sqlx::query("UPDATE user SET updated_at = (unixepoch()) WHERE id = $1")
+        .bind(recovery_code.user_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+    sqlx::query("DELETE FROM user_session WHERE user_id = $1")
+        .bind(recovery_code.user_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+    sqlx::query(
+        "INSERT INTO user_recovery_code (id, user_id, is_temporary) VALUES ($1, $2, FALSE)",
+    )
+    .bind(new_recovery_code_hash)
+    .bind(recovery_code.user_id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| e.to_string())?;
+    tx.commit().await.map_err(|e| e.to_string())?;
+
+    let session_token = create_user_session_query(db, recovery_code.user_id, DEFAULT_LIFETIME)
+        .await
+        .map_err(|_e| "Failed to create session.")?;
+    Ok((session_token, new_recovery_code))
+}
+
+pub async fn get_user_query(db: &Pool<Sqlite>, user_ref: Ref) -> Result<User, String> {
+    let query = match user_ref {
+        Ref::Id(id) => query_as::<_, User>("SELECT * FROM user WHERE id = $1").bind(id),
+        Ref::Name(username) => {
+            query_as::<_, User>("SELECT * FROM user WHERE username = $1").bind(username)
+        }
+    };
+    query.fetch_one(db).await.map_err(|e| e.to_string())
+}
+
+/// Returns one page of users along with the total number of users matching the filters.
+pub async fn get_users_query(
+    db: &Pool<Sqlite>,
+    page_size: i32,
+    offset: i64,
+    role: Option<Role>,
+) -> Result<(Vec<User>, i64), String> {
+    let total = query_scalar::<_, i64>("SELECT COUNT(*) FROM user WHERE ($1 IS NULL OR role = $1)")
+        .bind(&role)
+        .fetch_one(db)
+        .await
+        .map_err(|e| e.to_string())?;
+    let users = query_as::<_, User>(
+        "SELECT * FROM user WHERE ($1 IS NULL OR role = $1) ORDER BY id LIMIT $2 OFFSET $3",
+    )
+    .bind(role)
+    .bind(page_size)
+    .bind(offset)
+    .fetch_all(db)
+    .await
+    .map_err(|e| e.to_string())?;
+    Ok((users, total))
+}
+
+pub async fn update_user_role_query(
+    db: &Pool<Sqlite>,
+    user_id: i64,
+    role: Role,
+) -> Result<User, String> {
+    query_as::<_, User>(
+        "UPDATE user SET role = $1, updated_at = (unixepoch()) WHERE id = $2 RETURNING *",
+    )
+    .bind(role)
+    .bind(user_id)
+    .fetch_one(db)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Replaces a user's recovery code with a temporary one that is consumed when it is used to recover the user.
+pub async fn reset_user_recovery_code_query(
+    db: &Pool<Sqlite>,
+    user_id: i64,
+) -> Result<String, String> {
+    let (recovery_code, recovery_code_hash) =
+        generate_recovery_code().ok_or("Failed to generate recovery code.")?;
+    sqlx::query(
+        "INSERT INTO user_recovery_code (id, user_id, is_temporary) VALUES ($1, $2, TRUE) ON CONFLICT(user_id) DO UPDATE SET id=excluded.id, is_temporary=excluded.is_temporary",
+    )
+    .bind(recovery_code_hash)
+    .bind(user_id)
+    .execute(db)
+    .await
+    .map_err(|e| e.to_string())?;
+    Ok(recovery_code)
+}
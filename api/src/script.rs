@@ -1,8 +1,12 @@
 #![allow(dead_code)]
+#![allow(clippy::result_large_err)]
 mod model;
 mod queries;
 mod utils;
-use queries::data_import::data_import;
+use queries::{
+    client_data::export_client_data, data_export::export_content, data_import::data_import,
+    data_validate::validate_data,
+};
 use sqlx::{Sqlite, SqlitePool, migrate::MigrateDatabase};
 use std::{env, process};
 use utils::{new_sonyflake, next_id};
@@ -14,22 +18,35 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     match args.get(1) {
         Some(s) => match s.as_str() {
             "migrate" => {
-                migrate().await?;
+                migrate(args.iter().any(|arg| arg == "--prune")).await?;
             }
             "id_gen" => {
                 let id_count: u32 = args.get(2).unwrap_or(&"1".to_owned()).parse().unwrap_or(1);
                 id_gen(id_count).await?;
             }
+            "validate" => validate(),
+            "export_content" => {
+                let data_dir = args.get(2).map(|dir| dir.as_str()).unwrap_or("../data");
+                content_export(data_dir).await?;
+            }
+            "export_client_data" => {
+                let path = match args.get(2) {
+                    Some(path) => path.to_owned(),
+                    None => env::var("CLIENT_DATA_PATH")
+                        .unwrap_or_else(|_| "client_data.sqlite".to_owned()),
+                };
+                client_data_export(&path).await?;
+            }
             _ => {
                 println!(
-                    "Provided script name is not valid. Valid options are 'migrate' and 'id_gen'."
+                    "Provided script name is not valid. Valid options are 'migrate', 'validate', 'id_gen', 'export_content' and 'export_client_data'."
                 );
                 process::exit(1);
             }
         },
         None => {
             println!(
-                "Please provide a valid script name as the first argument. Valid options are 'migrate' and 'id_gen'."
+                "Please provide a valid script name as the first argument. Valid options are 'migrate', 'validate', 'id_gen', 'export_content' and 'export_client_data'."
             );
             process::exit(1);
         }
@@ -37,7 +54,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-async fn migrate() -> Result<(), Box<dyn std::error::Error>> {
+async fn migrate(prune: bool) -> Result<(), Box<dyn std::error::Error>> {
     let database_url =
         env::var("DATABASE_URL").expect("Environment variable DATABASE_URL not found");
     let api_migrator = sqlx::migrate!("./migrations");
@@ -61,7 +78,7 @@ async fn migrate() -> Result<(), Box<dyn std::error::Error>> {
     api_migrator.run(&db).await?;
 
     println!("\n  Importing data from data files.\n");
-    let version = data_import(&db, None).await.unwrap();
+    let version = data_import(&db, None, prune).await.unwrap();
     println!(
         "  Updated database for game version: '{} {}'.",
         version.game_id, version.game_version
@@ -70,6 +87,49 @@ async fn migrate() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+fn validate() {
+    println!("  Validating data files.\n");
+    let errors = validate_data("../data/content", "../data");
+    if !errors.is_empty() {
+        for error in &errors {
+            eprintln!("  {}", error);
+        }
+        eprintln!("\n  Found {} errors in data files.", errors.len());
+        process::exit(1);
+    }
+    println!("  No errors found in data files.");
+}
+
+async fn content_export(data_dir: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let database_url =
+        env::var("DATABASE_URL").expect("Environment variable DATABASE_URL not found");
+    let db = SqlitePool::connect(&database_url).await?;
+
+    println!("  Exporting content to {}.\n", data_dir);
+    export_content(&db, data_dir).await?;
+    println!("\n  Exported content.");
+
+    Ok(())
+}
+
+async fn client_data_export(path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let database_url =
+        env::var("DATABASE_URL").expect("Environment variable DATABASE_URL not found");
+    let db = SqlitePool::connect(&database_url).await?;
+
+    println!("  Exporting client data to {}.", path);
+    let export = export_client_data(&db, path).await?;
+    println!(
+        "  Exported {} contents and {} assets for game version: '{} {}'.",
+        export.content_count,
+        export.asset_count,
+        export.game_info.game_id,
+        export.game_info.game_version
+    );
+
+    Ok(())
+}
+
 async fn id_gen(id_count: u32) -> Result<(), Box<dyn std::error::Error>> {
     let machine_id_range =
         env::var("MACHINE_ID_RANGE").expect("Environment variable 'MACHINE_ID_RANGE' not found");
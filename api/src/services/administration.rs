@@ -0,0 +1,503 @@
+use crate::{
+    api::{
+        AccessToken, ByIdRequest, ByStringIdRequest, CreateAccessTokenReply,
+        CreateAccessTokenRequest, CreateGameServerRequest, CreateWorldRequest, GameServerData,
+        GetAccessTokensReply, GetAccessTokensRequest, GetGameServersReply, GetGameServersRequest,
+        GetUserRequest, GetUsersReply, GetUsersRequest, GetWorldsReply, GetWorldsRequest, Message,
+        ResetUserRecoveryCodeReply, RetireWorldRequest, UpdateGameServerRequest, UpdateUserRequest,
+        UpdateWorldRequest, User, VerifyAccessTokenRequest, World,
+        administration_server::Administration, get_user_request,
+    },
+    model::{
+        Ref,
+        authentication::AuthError,
+        fields::{AccessLevel, Role},
+    },
+    queries::{
+        administration::{
+            count_game_server_worlds_query, count_world_residents_query, create_access_token_query,
+            create_game_server_query, create_world_query, delete_access_token_query,
+            get_access_tokens_query, get_game_server_query, get_game_servers_query,
+            get_world_query, get_worlds_query, retire_game_server_query, retire_world_query,
+            update_game_server_query, update_world_query,
+        },
+        authentication::{delete_user_sessions_query, validate_access_token_query},
+        users::{
+            get_user_query, get_users_query, reset_user_recovery_code_query,
+            update_user_role_query, update_username_query,
+        },
+    },
+    utils::{
+        authentication::parse_access_token_id, page_count, parse_paging, transport::authenticate,
+        validate_and_format_name, validate_and_format_string_id, validate_region_code,
+    },
+};
+use sonyflake::Sonyflake;
+use sqlx::Sqlite;
+use sqlx::pool::Pool;
+use tonic::{Request, Response, Status};
+
+pub struct AdministrationService {
+    db: Pool<Sqlite>,
+    sf: Sonyflake,
+}
+
+impl AdministrationService {
+    pub fn new(db: Pool<Sqlite>, sf: Sonyflake) -> AdministrationService {
+        AdministrationService { db, sf }
+    }
+}
+
+#[tonic::async_trait]
+impl Administration for AdministrationService {
+    async fn create_access_token(
+        &self,
+        request: Request<CreateAccessTokenRequest>,
+    ) -> Result<Response<CreateAccessTokenReply>, Status> {
+        authenticate(&self.db, &request)
+            .await
+            .auth_access_level_or(AccessLevel::Administrator)?;
+        let args = request.into_inner();
+
+        let access_level = AccessLevel::try_from(args.access_level as u16)
+            .map_err(|_e| Status::invalid_argument("Invalid access_level."))?;
+        let (access_token, access_token_data) = create_access_token_query(
+            &self.db,
+            &self.sf,
+            access_level,
+            args.game_server_id,
+            args.lifetime_seconds,
+        )
+        .await
+        .map_err(Status::invalid_argument)?;
+        Ok(Response::new(CreateAccessTokenReply {
+            access_token,
+            access_token_data: Some(access_token_data.into()),
+        }))
+    }
+
+    async fn verify_access_token(
+        &self,
+        request: Request<VerifyAccessTokenRequest>,
+    ) -> Result<Response<AccessToken>, Status> {
+        authenticate(&self.db, &request)
+            .await
+            .auth_access_level_or(AccessLevel::Administrator)?;
+        let args = request.into_inner();
+
+        match validate_access_token_query(&self.db, &args.access_token).await {
+            Ok(access_token) => Ok(Response::new(access_token.into())),
+            Err(AuthError::Expired) => Err(Status::failed_precondition("Access token expired.")),
+            Err(AuthError::Invalid) => Err(Status::not_found("Access token is invalid.")),
+        }
+    }
+
+    async fn get_access_tokens(
+        &self,
+        request: Request<GetAccessTokensRequest>,
+    ) -> Result<Response<GetAccessTokensReply>, Status> {
+        authenticate(&self.db, &request)
+            .await
+            .auth_access_level_or(AccessLevel::Administrator)?;
+        let args = request.into_inner();
+
+        let id = match args.access_token {
+            Some(access_token) => Some(
+                parse_access_token_id(&access_token)
+                    .ok_or(Status::invalid_argument("Invalid access_token."))?,
+            ),
+            None => None,
+        };
+        let (page, page_size, offset) = parse_paging(args.page_size, args.page);
+        let (access_tokens, total) =
+            get_access_tokens_query(&self.db, page_size, offset, id, args.can_expire)
+                .await
+                .map_err(Status::internal)?;
+        Ok(Response::new(GetAccessTokensReply {
+            page,
+            page_count: page_count(total, page_size),
+            access_token_data_list: access_tokens.into_iter().map(|a| a.into()).collect(),
+        }))
+    }
+
+    async fn delete_access_token(
+        &self,
+        request: Request<ByIdRequest>,
+    ) -> Result<Response<Message>, Status> {
+        authenticate(&self.db, &request)
+            .await
+            .auth_access_level_or(AccessLevel::Administrator)?;
+        let args = request.into_inner();
+
+        if !delete_access_token_query(&self.db, args.id)
+            .await
+            .map_err(Status::internal)?
+        {
+            return Err(Status::not_found("Access token not found."));
+        }
+        Ok(Response::new(Message {
+            message: format!("Deleted access token {}.", args.id),
+        }))
+    }
+
+    async fn get_user(&self, request: Request<GetUserRequest>) -> Result<Response<User>, Status> {
+        authenticate(&self.db, &request)
+            .await
+            .auth_access_level_or(AccessLevel::Administrator)?;
+        let args = request.into_inner();
+
+        let user_ref = match args.r#ref {
+            Some(get_user_request::Ref::Id(id)) => Ref::Id(id),
+            Some(get_user_request::Ref::Username(username)) => Ref::Name(username),
+            None => return Err(Status::invalid_argument("Missing id or username.")),
+        };
+        let user = get_user_query(&self.db, user_ref)
+            .await
+            .map_err(|_e| Status::not_found("User not found."))?;
+        Ok(Response::new(User::try_from(user)?))
+    }
+
+    async fn get_users(
+        &self,
+        request: Request<GetUsersRequest>,
+    ) -> Result<Response<GetUsersReply>, Status> {
+        authenticate(&self.db, &request)
+            .await
+            .auth_access_level_or(AccessLevel::Administrator)?;
+        let args = request.into_inner();
+
+        let role = match args.role {
+            Some(role) => Some(
+                Role::try_from(role as u16)
+                    .map_err(|_e| Status::invalid_argument("Invalid role."))?,
+            ),
+            None => None,
+        };
+        let (page, page_size, offset) = parse_paging(args.page_size, args.page);
+        let (users, total) = get_users_query(&self.db, page_size, offset, role)
+            .await
+            .map_err(Status::internal)?;
+        Ok(Response::new(GetUsersReply {
+            page,
+            page_count: page_count(total, page_size),
+            user_list: users
+                .into_iter()
+                .map(User::try_from)
+                .collect::<Result<Vec<User>, Status>>()?,
+        }))
+    }
+
+    async fn update_user(
+        &self,
+        request: Request<UpdateUserRequest>,
+    ) -> Result<Response<User>, Status> {
+        authenticate(&self.db, &request)
+            .await
+            .auth_access_level_or(AccessLevel::Administrator)?;
+        let args = request.into_inner();
+
+        let mut user = get_user_query(&self.db, Ref::Id(args.id))
+            .await
+            .map_err(|_e| Status::not_found("User not found."))?;
+        if let Some(username) = args.username {
+            user = update_username_query(&self.db, user.id, username)
+                .await
+                .map_err(Status::invalid_argument)?;
+        }
+        if let Some(role) = args.role {
+            let role = Role::try_from(role as u16)
+                .map_err(|_e| Status::invalid_argument("Invalid role."))?;
+            user = update_user_role_query(&self.db, user.id, role)
+                .await
+                .map_err(Status::internal)?;
+        }
+        Ok(Response::new(User::try_from(user)?))
+    }
+
+    async fn log_out_user(
+        &self,
+        request: Request<ByIdRequest>,
+    ) -> Result<Response<Message>, Status> {
+        authenticate(&self.db, &request)
+            .await
+            .auth_access_level_or(AccessLevel::Administrator)?;
+        let args = request.into_inner();
+
+        let count = delete_user_sessions_query(&self.db, args.id, None)
+            .await
+            .map_err(Status::internal)?;
+        Ok(Response::new(Message {
+            message: format!("Logged out of {} sessions.", count),
+        }))
+    }
+
+    async fn reset_user_recovery_code(
+        &self,
+        request: Request<ByIdRequest>,
+    ) -> Result<Response<ResetUserRecoveryCodeReply>, Status> {
+        authenticate(&self.db, &request)
+            .await
+            .auth_access_level_or(AccessLevel::Administrator)?;
+        let args = request.into_inner();
+
+        let user = get_user_query(&self.db, Ref::Id(args.id))
+            .await
+            .map_err(|_e| Status::not_found("User not found."))?;
+        let recovery_code = reset_user_recovery_code_query(&self.db, user.id)
+            .await
+            .map_err(Status::internal)?;
+        Ok(Response::new(ResetUserRecoveryCodeReply { recovery_code }))
+    }
+
+    async fn create_game_server(
+        &self,
+        request: Request<CreateGameServerRequest>,
+    ) -> Result<Response<GameServerData>, Status> {
+        authenticate(&self.db, &request)
+            .await
+            .auth_access_level_or(AccessLevel::Administrator)?;
+        let args = request.into_inner();
+
+        let id = validate_and_format_string_id(&args.id)
+            .ok_or(Status::invalid_argument("Invalid id."))?;
+        if !validate_region_code(&args.region_code) {
+            return Err(Status::invalid_argument(
+                "region_code must be a tz database identifier.",
+            ));
+        }
+        let display_name = validate_and_format_name(args.display_name)
+            .ok_or(Status::invalid_argument("Invalid display_name."))?;
+        if get_game_server_query(&self.db, &id).await.is_ok() {
+            return Err(Status::already_exists("Game server already exists."));
+        }
+        let game_server = create_game_server_query(&self.db, &id, &args.region_code, &display_name)
+            .await
+            .map_err(Status::internal)?;
+        Ok(Response::new(game_server.into()))
+    }
+
+    async fn update_game_server(
+        &self,
+        request: Request<UpdateGameServerRequest>,
+    ) -> Result<Response<GameServerData>, Status> {
+        authenticate(&self.db, &request)
+            .await
+            .auth_access_level_or(AccessLevel::Administrator)?;
+        let args = request.into_inner();
+
+        if args
+            .region_code
+            .as_deref()
+            .is_some_and(|region_code| !validate_region_code(region_code))
+        {
+            return Err(Status::invalid_argument(
+                "region_code must be a tz database identifier.",
+            ));
+        }
+        let display_name = match args.display_name {
+            Some(display_name) => Some(
+                validate_and_format_name(display_name)
+                    .ok_or(Status::invalid_argument("Invalid display_name."))?,
+            ),
+            None => None,
+        };
+        get_game_server_query(&self.db, &args.id)
+            .await
+            .map_err(|_e| Status::not_found("Game server not found."))?;
+        let game_server = update_game_server_query(
+            &self.db,
+            &args.id,
+            args.region_code.as_deref(),
+            display_name.as_deref(),
+        )
+        .await
+        .map_err(Status::internal)?;
+        Ok(Response::new(game_server.into()))
+    }
+
+    async fn get_game_servers(
+        &self,
+        request: Request<GetGameServersRequest>,
+    ) -> Result<Response<GetGameServersReply>, Status> {
+        authenticate(&self.db, &request)
+            .await
+            .auth_access_level_or(AccessLevel::Administrator)?;
+        let args = request.into_inner();
+
+        let (page, page_size, offset) = parse_paging(args.page_size, args.page);
+        let (game_servers, total) = get_game_servers_query(&self.db, page_size, offset)
+            .await
+            .map_err(Status::internal)?;
+        Ok(Response::new(GetGameServersReply {
+            page,
+            page_count: page_count(total, page_size),
+            game_server_list: game_servers.into_iter().map(|g| g.into()).collect(),
+        }))
+    }
+
+    async fn retire_game_server(
+        &self,
+        request: Request<ByStringIdRequest>,
+    ) -> Result<Response<Message>, Status> {
+        authenticate(&self.db, &request)
+            .await
+            .auth_access_level_or(AccessLevel::Administrator)?;
+        let args = request.into_inner();
+
+        let game_server = get_game_server_query(&self.db, &args.id)
+            .await
+            .map_err(|_e| Status::not_found("Game server not found."))?;
+        let world_count = count_game_server_worlds_query(&self.db, &game_server.id)
+            .await
+            .map_err(Status::internal)?;
+        if world_count > 0 {
+            return Err(Status::failed_precondition(format!(
+                "Game server still hosts {} worlds. Move or retire them first.",
+                world_count
+            )));
+        }
+        let access_token_count = retire_game_server_query(&self.db, &game_server.id)
+            .await
+            .map_err(Status::internal)?;
+        Ok(Response::new(Message {
+            message: format!(
+                "Retired game server {} and deleted {} access tokens.",
+                game_server.id, access_token_count
+            ),
+        }))
+    }
+
+    async fn create_world(
+        &self,
+        request: Request<CreateWorldRequest>,
+    ) -> Result<Response<World>, Status> {
+        authenticate(&self.db, &request)
+            .await
+            .auth_access_level_or(AccessLevel::Administrator)?;
+        let args = request.into_inner();
+
+        let id = validate_and_format_string_id(&args.id)
+            .ok_or(Status::invalid_argument("Invalid id."))?;
+        let display_name = validate_and_format_name(args.display_name)
+            .ok_or(Status::invalid_argument("Invalid display_name."))?;
+        let game_server = get_game_server_query(&self.db, &args.game_server_id)
+            .await
+            .map_err(|_e| Status::not_found("Game server not found."))?;
+        if get_world_query(&self.db, &id).await.is_ok() {
+            return Err(Status::already_exists("World already exists."));
+        }
+        let world = create_world_query(&self.db, &id, &game_server.id, &display_name)
+            .await
+            .map_err(Status::internal)?;
+        Ok(Response::new(world.into()))
+    }
+
+    async fn update_world(
+        &self,
+        request: Request<UpdateWorldRequest>,
+    ) -> Result<Response<World>, Status> {
+        authenticate(&self.db, &request)
+            .await
+            .auth_access_level_or(AccessLevel::Administrator)?;
+        let args = request.into_inner();
+
+        let display_name = match args.display_name {
+            Some(display_name) => Some(
+                validate_and_format_name(display_name)
+                    .ok_or(Status::invalid_argument("Invalid display_name."))?,
+            ),
+            None => None,
+        };
+        get_world_query(&self.db, &args.id)
+            .await
+            .map_err(|_e| Status::not_found("World not found."))?;
+        let game_server_id = match args.game_server_id {
+            Some(game_server_id) => Some(
+                get_game_server_query(&self.db, &game_server_id)
+                    .await
+                    .map_err(|_e| Status::not_found("Game server not found."))?
+                    .id,
+            ),
+            None => None,
+        };
+        let world = update_world_query(
+            &self.db,
+            &args.id,
+            game_server_id.as_deref(),
+            display_name.as_deref(),
+        )
+        .await
+        .map_err(Status::internal)?;
+        Ok(Response::new(world.into()))
+    }
+
+    async fn get_worlds(
+        &self,
+        request: Request<GetWorldsRequest>,
+    ) -> Result<Response<GetWorldsReply>, Status> {
+        authenticate(&self.db, &request)
+            .await
+            .auth_access_level_or(AccessLevel::Administrator)?;
+        let args = request.into_inner();
+
+        let (page, page_size, offset) = parse_paging(args.page_size, args.page);
+        let (worlds, total) =
+            get_worlds_query(&self.db, page_size, offset, args.game_server_id.as_deref())
+                .await
+                .map_err(Status::internal)?;
+        Ok(Response::new(GetWorldsReply {
+            page,
+            page_count: page_count(total, page_size),
+            world_list: worlds.into_iter().map(|w| w.into()).collect(),
+        }))
+    }
+
+    async fn retire_world(
+        &self,
+        request: Request<RetireWorldRequest>,
+    ) -> Result<Response<Message>, Status> {
+        authenticate(&self.db, &request)
+            .await
+            .auth_access_level_or(AccessLevel::Administrator)?;
+        let args = request.into_inner();
+
+        let world = get_world_query(&self.db, &args.id)
+            .await
+            .map_err(|_e| Status::not_found("World not found."))?;
+        let migrate_to_world_id = match args.migrate_to_world_id {
+            Some(migrate_to_world_id) => {
+                let target = get_world_query(&self.db, &migrate_to_world_id)
+                    .await
+                    .map_err(|_e| Status::not_found("Migration target world not found."))?;
+                if target.id.eq_ignore_ascii_case(&world.id) {
+                    return Err(Status::invalid_argument(
+                        "Cannot migrate a world into itself.",
+                    ));
+                }
+                Some(target.id)
+            }
+            None => None,
+        };
+        if migrate_to_world_id.is_none() {
+            let (character_count, guild_count) = count_world_residents_query(&self.db, &world.id)
+                .await
+                .map_err(Status::internal)?;
+            if character_count > 0 || guild_count > 0 {
+                return Err(Status::failed_precondition(format!(
+                    "World is still home to {} characters and {} guilds. Provide migrate_to_world_id.",
+                    character_count, guild_count
+                )));
+            }
+        }
+        let (character_count, guild_count) =
+            retire_world_query(&self.db, &world.id, migrate_to_world_id.as_deref())
+                .await
+                .map_err(Status::failed_precondition)?;
+        Ok(Response::new(Message {
+            message: format!(
+                "Retired world {} and migrated {} characters and {} guilds.",
+                world.id, character_count, guild_count
+            ),
+        }))
+    }
+}
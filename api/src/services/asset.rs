@@ -0,0 +1,459 @@
+use crate::{
+    api::{
+        AssetDataChunk, AssetFile, Content, ContentChange, GameInfo, GetAssetDataRequest,
+        GetAssetRequest, GetAssetUrlReply, GetAssetsReply, GetAssetsRequest, GetContentRequest,
+        GetContentsReply, GetContentsRequest, GetGameInfoRequest, SyncContentReply,
+        SyncContentRequest, UploadAssetChunk, WatchContentChangesRequest, asset_server::Asset,
+        get_asset_data_request, get_asset_request, get_content_request,
+    },
+    model::{
+        self, Ref, UploadError,
+        fields::{ContentSubtype, ContentType},
+    },
+    queries::{
+        asset::{
+            get_asset_header_query, get_asset_query, get_assets_query, get_content_query,
+            get_contents_query, read_asset_data_query, resolve_content_query, sync_content_query,
+            upload_asset_query,
+        },
+        game_info::get_game_info_query,
+    },
+    utils::{
+        USER_ASSET_PATH_PREFIX, authentication::id_to_base32, current_timestamp, get_magic_cookie,
+        page_count, parse_paging, read_asset_data, transport::authenticate,
+        validate_and_format_user_asset_path,
+    },
+};
+use sonyflake::Sonyflake;
+use sqlx::Sqlite;
+use sqlx::pool::Pool;
+use std::pin::Pin;
+use tokio::sync::{
+    broadcast::{self, error::RecvError},
+    mpsc,
+};
+use tokio_stream::{Stream, StreamExt, wrappers::ReceiverStream};
+use tonic::{Request, Response, Status, Streaming};
+
+/// Size in bytes of each chunk read from `Asset.data` when streaming it.
+const ASSET_DATA_CHUNK_SIZE: usize = 64 * 1024;
+/// MIME types users can upload, along with the largest size in bytes allowed for each.
+const USER_ASSET_TYPES: [(&str, i64); 6] = [
+    ("image/png", 1024 * 1024),
+    ("image/jpeg", 1024 * 1024),
+    ("image/webp", 1024 * 1024),
+    ("audio/ogg", 2 * 1024 * 1024),
+    ("text/plain", 64 * 1024),
+    ("application/json", 64 * 1024),
+];
+/// Total size in bytes of the assets each user can upload.
+const USER_ASSET_QUOTA: i64 = 16 * 1024 * 1024;
+
+pub struct AssetService {
+    db: Pool<Sqlite>,
+    sf: Sonyflake,
+    asset_base_url: String,
+    /// Only set in watch mode.
+    content_changes: Option<broadcast::Sender<model::ContentChange>>,
+}
+
+impl AssetService {
+    pub fn new(
+        db: Pool<Sqlite>,
+        sf: Sonyflake,
+        asset_base_url: String,
+        content_changes: Option<broadcast::Sender<model::ContentChange>>,
+    ) -> AssetService {
+        AssetService {
+            db,
+            sf,
+            asset_base_url,
+            content_changes,
+        }
+    }
+}
+
+/// Reads `Asset.data` from `offset` up to `end` on a separate task, sending `(offset, data)` chunks of at most `ASSET_DATA_CHUNK_SIZE` bytes.
+/// At least one chunk is always sent, and reading stops early once the receiver is dropped.
+pub fn spawn_asset_data_reader(
+    db: Pool<Sqlite>,
+    asset_id: i64,
+    mut offset: i64,
+    end: i64,
+) -> ReceiverStream<Result<(i64, Vec<u8>), String>> {
+    let (tx, rx) = mpsc::channel(4);
+    tokio::spawn(async move {
+        loop {
+            let length = (end - offset).clamp(0, ASSET_DATA_CHUNK_SIZE as i64) as usize;
+            let data = match read_asset_data_query(&db, asset_id, offset, length).await {
+                Ok((data, _)) => data,
+                Err(e) => {
+                    let _ = tx.send(Err(e)).await;
+                    break;
+                }
+            };
+            let chunk_offset = offset;
+            offset += data.len() as i64;
+            // Data can end early if the asset was replaced while it was being read.
+            let is_last = data.is_empty() || offset >= end;
+            if tx.send(Ok((chunk_offset, data))).await.is_err() || is_last {
+                break;
+            }
+        }
+    });
+    ReceiverStream::new(rx)
+}
+
+fn parse_asset_ref(asset_ref: Option<get_asset_request::Ref>) -> Result<Ref, Status> {
+    match asset_ref {
+        Some(get_asset_request::Ref::Id(id)) => Ok(Ref::Id(id)),
+        Some(get_asset_request::Ref::Path(path)) => Ok(Ref::Name(path)),
+        None => Err(Status::invalid_argument("Missing id or path.")),
+    }
+}
+
+fn parse_content_type(content_type: i32) -> Result<ContentType, Status> {
+    ContentType::try_from(content_type as u16)
+        .map_err(|_e| Status::invalid_argument("Invalid content_type."))
+}
+
+#[tonic::async_trait]
+impl Asset for AssetService {
+    type GetAssetDataStream = Pin<Box<dyn Stream<Item = Result<AssetDataChunk, Status>> + Send>>;
+    type WatchContentChangesStream =
+        Pin<Box<dyn Stream<Item = Result<ContentChange, Status>> + Send>>;
+
+    async fn get_game_info(
+        &self,
+        _request: Request<GetGameInfoRequest>,
+    ) -> Result<Response<GameInfo>, Status> {
+        let game_info = get_game_info_query(&self.db)
+            .await
+            .ok_or(Status::internal("No game_info in database."))?;
+        Ok(Response::new(game_info.into()))
+    }
+
+    async fn get_content(
+        &self,
+        request: Request<GetContentRequest>,
+    ) -> Result<Response<Content>, Status> {
+        let args = request.into_inner();
+
+        let content_ref = match args.r#ref {
+            Some(get_content_request::Ref::Id(id)) => Ref::Id(id),
+            Some(get_content_request::Ref::Name(name)) => Ref::Name(name),
+            None => return Err(Status::invalid_argument("Missing id or name.")),
+        };
+        let content_type = args.content_type.map(parse_content_type).transpose()?;
+        let content = get_content_query(&self.db, content_ref, content_type)
+            .await
+            .map_err(Status::not_found)?;
+        Ok(Response::new(content.into()))
+    }
+
+    async fn get_resolved_content(
+        &self,
+        request: Request<GetContentRequest>,
+    ) -> Result<Response<Content>, Status> {
+        let args = request.into_inner();
+
+        let content_ref = match args.r#ref {
+            Some(get_content_request::Ref::Id(id)) => Ref::Id(id),
+            Some(get_content_request::Ref::Name(name)) => Ref::Name(name),
+            None => return Err(Status::invalid_argument("Missing id or name.")),
+        };
+        let content_type = args.content_type.map(parse_content_type).transpose()?;
+        let content = get_content_query(&self.db, content_ref, content_type)
+            .await
+            .map_err(Status::not_found)?;
+        let content = resolve_content_query(&self.db, content)
+            .await
+            .map_err(Status::failed_precondition)?;
+        Ok(Response::new(content.into()))
+    }
+
+    async fn get_contents(
+        &self,
+        request: Request<GetContentsRequest>,
+    ) -> Result<Response<GetContentsReply>, Status> {
+        let args = request.into_inner();
+
+        let content_type = args.content_type.map(parse_content_type).transpose()?;
+        let content_subtype = match args.content_subtype {
+            Some(content_subtype) => Some(
+                ContentSubtype::try_from(content_subtype as u16)
+                    .map_err(|_e| Status::invalid_argument("Invalid content_subtype."))?,
+            ),
+            None => None,
+        };
+        let (page, page_size, offset) = parse_paging(args.page_size, args.page);
+        let (contents, total) =
+            get_contents_query(&self.db, page_size, offset, content_type, content_subtype)
+                .await
+                .map_err(Status::internal)?;
+        Ok(Response::new(GetContentsReply {
+            page,
+            page_count: page_count(total, page_size),
+            content_list: contents.into_iter().map(|c| c.into()).collect(),
+        }))
+    }
+
+    async fn get_asset(
+        &self,
+        request: Request<GetAssetRequest>,
+    ) -> Result<Response<AssetFile>, Status> {
+        let args = request.into_inner();
+
+        let asset = get_asset_query(
+            &self.db,
+            parse_asset_ref(args.r#ref)?,
+            args.if_none_match.as_deref(),
+        )
+        .await
+        .map_err(|_e| Status::not_found("Asset not found."))?;
+        let mut asset = AssetFile::from(asset);
+        if !asset.hash.is_empty() && args.if_none_match.is_some_and(|hash| hash == asset.hash) {
+            asset.data = None;
+        }
+        Ok(Response::new(asset))
+    }
+
+    async fn get_assets(
+        &self,
+        request: Request<GetAssetsRequest>,
+    ) -> Result<Response<GetAssetsReply>, Status> {
+        let args = request.into_inner();
+
+        let include_data = args.include_data.unwrap_or(false);
+        let (page, page_size, offset) = parse_paging(args.page_size, args.page);
+        let (assets, total) = get_assets_query(
+            &self.db,
+            page_size,
+            offset,
+            args.path_prefix.as_deref(),
+            include_data,
+        )
+        .await
+        .map_err(Status::internal)?;
+        Ok(Response::new(GetAssetsReply {
+            page,
+            page_count: page_count(total, page_size),
+            asset_list: assets
+                .into_iter()
+                .map(|a| {
+                    let mut asset = AssetFile::from(a);
+                    if !include_data {
+                        asset.data = None;
+                    }
+                    asset
+                })
+                .collect(),
+        }))
+    }
+
+    async fn get_asset_url(
+        &self,
+        request: Request<GetAssetRequest>,
+    ) -> Result<Response<GetAssetUrlReply>, Status> {
+        let args = request.into_inner();
+
+        let asset = get_asset_header_query(&self.db, parse_asset_ref(args.r#ref)?)
+            .await
+            .map_err(|_e| Status::not_found("Asset not found."))?;
+        Ok(Response::new(GetAssetUrlReply {
+            url: format!(
+                "{}/{}",
+                self.asset_base_url.trim_end_matches('/'),
+                asset.path.trim_start_matches('/')
+            ),
+        }))
+    }
+
+    async fn get_asset_data(
+        &self,
+        request: Request<GetAssetDataRequest>,
+    ) -> Result<Response<Self::GetAssetDataStream>, Status> {
+        let args = request.into_inner();
+
+        let asset_ref = match args.r#ref {
+            Some(get_asset_data_request::Ref::Id(id)) => Ref::Id(id),
+            Some(get_asset_data_request::Ref::Path(path)) => Ref::Name(path),
+            None => return Err(Status::invalid_argument("Missing id or path.")),
+        };
+        let asset = get_asset_header_query(&self.db, asset_ref)
+            .await
+            .map_err(|_e| Status::not_found("Asset not found."))?;
+        let mut offset = args.offset.unwrap_or(0);
+        if !(0..=asset.size).contains(&offset) {
+            return Err(Status::out_of_range("Offset is outside of the asset data."));
+        }
+        // A cached copy is current, so skip straight to the end of the data. An empty hash is unknown and never matches.
+        if !asset.hash.is_empty() && args.if_none_match.is_some_and(|hash| hash == asset.hash) {
+            offset = asset.size;
+        }
+
+        let (size, hash) = (asset.size, asset.hash);
+        let chunks =
+            spawn_asset_data_reader(self.db.clone(), asset.id, offset, size).map(move |chunk| {
+                chunk
+                    .map(|(offset, data)| AssetDataChunk {
+                        offset,
+                        size,
+                        data,
+                        hash: hash.clone(),
+                    })
+                    .map_err(Status::internal)
+            });
+        Ok(Response::new(Box::pin(chunks)))
+    }
+
+    async fn sync_content(
+        &self,
+        request: Request<SyncContentRequest>,
+    ) -> Result<Response<SyncContentReply>, Status> {
+        let args = request.into_inner();
+
+        let game_info = get_game_info_query(&self.db)
+            .await
+            .ok_or(Status::internal("No game_info in database."))?;
+        // Tombstones don't survive the database being recreated, so caches older than it or from another game version are rebuilt from scratch.
+        let is_full_sync = args.since <= 0
+            || args.game_version != game_info.game_version
+            || args.since < game_info.created_at.and_utc().timestamp();
+        let since = if is_full_sync { 0 } else { args.since };
+        // Taken before reading so rows updated during this sync are sent again next time.
+        let synced_at = current_timestamp();
+        let include_asset_data = args.include_asset_data.unwrap_or(false);
+        let sync = sync_content_query(&self.db, since, include_asset_data)
+            .await
+            .map_err(Status::internal)?;
+        Ok(Response::new(SyncContentReply {
+            synced_at,
+            game_version: game_info.game_version,
+            is_full_sync,
+            content_list: sync.contents.into_iter().map(|c| c.into()).collect(),
+            asset_list: sync
+                .assets
+                .into_iter()
+                .map(|a| {
+                    let mut asset = AssetFile::from(a);
+                    if !include_asset_data {
+                        asset.data = None;
+                    }
+                    asset
+                })
+                .collect(),
+            deleted_content_ids: sync.deleted_content_ids,
+            deleted_asset_ids: sync.deleted_asset_ids,
+        }))
+    }
+
+    async fn watch_content_changes(
+        &self,
+        _request: Request<WatchContentChangesRequest>,
+    ) -> Result<Response<Self::WatchContentChangesStream>, Status> {
+        let mut changes = self
+            .content_changes
+            .as_ref()
+            .ok_or(Status::failed_precondition(
+                "Watch mode is only available for dev game versions.",
+            ))?
+            .subscribe();
+
+        let (tx, rx) = mpsc::channel(4);
+        tokio::spawn(async move {
+            loop {
+                let change = tokio::select! {
+                    change = changes.recv() => change,
+                    _ = tx.closed() => break,
+                };
+                match change {
+                    Ok(change) => {
+                        if tx.send(Ok(change.into())).await.is_err() {
+                            break;
+                        }
+                    }
+                    // Missed changes are covered by the next one, clients sync everything since their last sync anyway.
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+        Ok(Response::new(Box::pin(ReceiverStream::new(rx))))
+    }
+
+    async fn upload_asset(
+        &self,
+        request: Request<Streaming<UploadAssetChunk>>,
+    ) -> Result<Response<AssetFile>, Status> {
+        // `Streaming` isn't `Sync`, so the metadata is split off to authenticate with.
+        let (metadata, extensions, mut chunks) = request.into_parts();
+        let (user, _) = authenticate(&self.db, &Request::from_parts(metadata, extensions, ()))
+            .await
+            .auth_session_or()?;
+
+        let max_size = USER_ASSET_TYPES
+            .iter()
+            .map(|(_, size)| *size)
+            .max()
+            .unwrap_or(0);
+        let mut path = None;
+        let mut data = Vec::new();
+        while let Some(chunk) = chunks.message().await? {
+            path.get_or_insert(chunk.path);
+            // Checked while receiving so oversized uploads are never fully buffered.
+            if (data.len() + chunk.data.len()) as i64 > max_size {
+                return Err(Status::invalid_argument(format!(
+                    "Asset is larger than the {} byte limit.",
+                    max_size
+                )));
+            }
+            data.extend_from_slice(&chunk.data);
+        }
+        if data.is_empty() {
+            return Err(Status::invalid_argument("Asset is empty."));
+        }
+        let path = path
+            .as_deref()
+            .and_then(validate_and_format_user_asset_path)
+            .ok_or(Status::invalid_argument(
+                "Path must be 1 to 8 '/' separated names of letters, digits, '.', '_' or '-'.",
+            ))?;
+        let handle =
+            id_to_base32(user.handle).ok_or(Status::internal("Failed to encode handle."))?;
+        let path = format!("{}{}/{}", USER_ASSET_PATH_PREFIX, handle, path);
+
+        // The magic cookie can't be sent between threads, so it's dropped before the next await.
+        let file = read_asset_data(data, &get_magic_cookie())
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let (_, size, file_type, _) = &file;
+        let max_type_size = USER_ASSET_TYPES
+            .iter()
+            .find(|(allowed_type, _)| allowed_type == file_type)
+            .map(|(_, size)| *size)
+            .ok_or(Status::invalid_argument(format!(
+                "Assets of type '{}' can't be uploaded.",
+                file_type
+            )))?;
+        if *size > max_type_size {
+            return Err(Status::invalid_argument(format!(
+                "Assets of type '{}' are limited to {} bytes.",
+                file_type, max_type_size
+            )));
+        }
+
+        let asset = upload_asset_query(&self.db, &self.sf, user.id, path, file, USER_ASSET_QUOTA)
+            .await
+            .map_err(|e| match e {
+                UploadError::QuotaExceeded { used, quota } => Status::resource_exhausted(format!(
+                    "Asset quota exceeded, {} of {} bytes are already used.",
+                    used, quota
+                )),
+                UploadError::PathInUse => Status::already_exists("Asset path is already in use."),
+                UploadError::Internal(e) => Status::internal(e),
+            })?;
+        let mut asset = AssetFile::from(asset);
+        asset.data = None;
+        Ok(Response::new(asset))
+    }
+}
@@ -1,8 +1,30 @@
 use crate::api::Message;
-use crate::api::{game_data_server::GameData, AddItemRequest};
+use crate::api::{
+    ActivateGearsetRequest, AddItemReply, AddItemRequest, Appearance, ApplyGlamourRequest,
+    ApplyOutfitRequest, CreateOutfitRequest, DeleteOutfitRequest, DiscardItemRequest,
+    EquipItemRequest, GearsetReply, GetAppearanceRequest, GetGearsetsReply, GetGearsetsRequest,
+    GetItemCollectionReply, GetItemCollectionRequest, GetOutfitsReply, GetOutfitsRequest,
+    ItemChangeReply, LinkGearsetRequest, MergeStacksRequest, MoveItemRequest, OutfitReply,
+    RenameOutfitRequest, SaveGearsetRequest, SplitStackRequest, UnequipItemRequest,
+    game_data_server::GameData, gear_slot_list,
+};
+use crate::model::fields::{AccessLevel, ItemCollectionEntryLocation, ItemLocation, ItemQuality};
+use crate::queries::gearset::{
+    activate_gearset_query, equip_item_query, get_gearsets_query, link_gearset_query,
+    save_gearset_query, unequip_item_query,
+};
+use crate::queries::inventory::{
+    add_item_query, discard_item_query, get_item_collection_query, merge_stacks_query,
+    move_item_query, split_stack_query,
+};
+use crate::queries::outfit::{
+    apply_glamour_query, apply_outfit_query, create_outfit_query, delete_outfit_query,
+    get_appearance_query, get_outfits_query, rename_outfit_query,
+};
+use crate::utils::{page_count, parse_paging, transport::authenticate};
 use sonyflake::Sonyflake;
-use sqlx::pool::Pool;
 use sqlx::Sqlite;
+use sqlx::pool::Pool;
 use tonic::{Request, Response, Status};
 
 pub struct GameDataService {
@@ -45,13 +67,369 @@ impl GameData for GameDataService {
             message: format!("Request: {:?}", request),
         }))
     }
-    /// TODO
     async fn add_item(
         &self,
         request: Request<AddItemRequest>,
-    ) -> Result<Response<Message>, Status> {
-        Ok(Response::new(Message {
-            message: format!("Request: {:?}", request),
+    ) -> Result<Response<AddItemReply>, Status> {
+        authenticate(&self.db, &request)
+            .await
+            .auth_access_level_or(AccessLevel::GameServer)?;
+        let args = request.into_inner();
+
+        let location = ItemLocation::try_from(args.location as u16)
+            .map_err(|_e| Status::invalid_argument("Invalid location."))?;
+        let quality = ItemQuality::try_from(args.quality as u16)
+            .map_err(|_e| Status::invalid_argument("Invalid quality."))?;
+        let items = add_item_query(
+            &self.db,
+            &self.sf,
+            args.character_id,
+            args.item_content_id,
+            args.quantity,
+            location,
+            quality,
+        )
+        .await
+        .map_err(Status::from)?;
+        Ok(Response::new(AddItemReply {
+            item_list: items.into_iter().map(|i| i.into()).collect(),
+        }))
+    }
+    async fn move_item(
+        &self,
+        request: Request<MoveItemRequest>,
+    ) -> Result<Response<ItemChangeReply>, Status> {
+        authenticate(&self.db, &request)
+            .await
+            .auth_access_level_or(AccessLevel::GameServer)?;
+        let args = request.into_inner();
+
+        let location = ItemLocation::try_from(args.location as u16)
+            .map_err(|_e| Status::invalid_argument("Invalid location."))?;
+        let changes = move_item_query(
+            &self.db,
+            &self.sf,
+            args.character_id,
+            args.item_id,
+            location,
+            args.container_item_id,
+        )
+        .await
+        .map_err(Status::from)?;
+        Ok(Response::new(changes.into()))
+    }
+    async fn split_stack(
+        &self,
+        request: Request<SplitStackRequest>,
+    ) -> Result<Response<ItemChangeReply>, Status> {
+        authenticate(&self.db, &request)
+            .await
+            .auth_access_level_or(AccessLevel::GameServer)?;
+        let args = request.into_inner();
+
+        let changes = split_stack_query(
+            &self.db,
+            &self.sf,
+            args.character_id,
+            args.item_id,
+            args.quantity,
+        )
+        .await
+        .map_err(Status::from)?;
+        Ok(Response::new(changes.into()))
+    }
+    async fn merge_stacks(
+        &self,
+        request: Request<MergeStacksRequest>,
+    ) -> Result<Response<ItemChangeReply>, Status> {
+        authenticate(&self.db, &request)
+            .await
+            .auth_access_level_or(AccessLevel::GameServer)?;
+        let args = request.into_inner();
+
+        let changes = merge_stacks_query(
+            &self.db,
+            &self.sf,
+            args.character_id,
+            args.source_item_id,
+            args.target_item_id,
+        )
+        .await
+        .map_err(Status::from)?;
+        Ok(Response::new(changes.into()))
+    }
+    async fn discard_item(
+        &self,
+        request: Request<DiscardItemRequest>,
+    ) -> Result<Response<ItemChangeReply>, Status> {
+        authenticate(&self.db, &request)
+            .await
+            .auth_access_level_or(AccessLevel::GameServer)?;
+        let args = request.into_inner();
+
+        let changes = discard_item_query(
+            &self.db,
+            &self.sf,
+            args.character_id,
+            args.item_id,
+            args.quantity,
+        )
+        .await
+        .map_err(Status::from)?;
+        Ok(Response::new(changes.into()))
+    }
+    async fn get_item_collection(
+        &self,
+        request: Request<GetItemCollectionRequest>,
+    ) -> Result<Response<GetItemCollectionReply>, Status> {
+        authenticate(&self.db, &request)
+            .await
+            .auth_access_level_or(AccessLevel::GameServer)?;
+        let args = request.into_inner();
+
+        let location = match args.location {
+            Some(location) => Some(
+                ItemCollectionEntryLocation::try_from(location as u16)
+                    .map_err(|_e| Status::invalid_argument("Invalid location."))?,
+            ),
+            None => None,
+        };
+        let (page, page_size, offset) = parse_paging(args.page_size, args.page);
+        let (entries, total) =
+            get_item_collection_query(&self.db, args.character_id, page_size, offset, location)
+                .await
+                .map_err(Status::from)?;
+        Ok(Response::new(GetItemCollectionReply {
+            page,
+            page_count: page_count(total, page_size),
+            item_collection_entry_list: entries.into_iter().map(|e| e.into()).collect(),
+        }))
+    }
+    async fn equip_item(
+        &self,
+        request: Request<EquipItemRequest>,
+    ) -> Result<Response<GearsetReply>, Status> {
+        authenticate(&self.db, &request)
+            .await
+            .auth_access_level_or(AccessLevel::GameServer)?;
+        let args = request.into_inner();
+
+        let changes = equip_item_query(
+            &self.db,
+            &self.sf,
+            args.character_id,
+            args.item_id,
+            args.gearset_id,
+        )
+        .await
+        .map_err(Status::from)?;
+        Ok(Response::new(changes.into()))
+    }
+    async fn unequip_item(
+        &self,
+        request: Request<UnequipItemRequest>,
+    ) -> Result<Response<GearsetReply>, Status> {
+        authenticate(&self.db, &request)
+            .await
+            .auth_access_level_or(AccessLevel::GameServer)?;
+        let args = request.into_inner();
+
+        let changes = unequip_item_query(
+            &self.db,
+            &self.sf,
+            args.character_id,
+            args.slot,
+            args.gearset_id,
+        )
+        .await
+        .map_err(Status::from)?;
+        Ok(Response::new(changes.into()))
+    }
+    async fn save_gearset(
+        &self,
+        request: Request<SaveGearsetRequest>,
+    ) -> Result<Response<GearsetReply>, Status> {
+        authenticate(&self.db, &request)
+            .await
+            .auth_access_level_or(AccessLevel::GameServer)?;
+        let args = request.into_inner();
+
+        let changes = save_gearset_query(&self.db, &self.sf, args.character_id, args.name)
+            .await
+            .map_err(Status::from)?;
+        Ok(Response::new(changes.into()))
+    }
+    async fn activate_gearset(
+        &self,
+        request: Request<ActivateGearsetRequest>,
+    ) -> Result<Response<GearsetReply>, Status> {
+        authenticate(&self.db, &request)
+            .await
+            .auth_access_level_or(AccessLevel::GameServer)?;
+        let args = request.into_inner();
+
+        let changes =
+            activate_gearset_query(&self.db, &self.sf, args.character_id, args.gearset_id)
+                .await
+                .map_err(Status::from)?;
+        Ok(Response::new(changes.into()))
+    }
+    async fn link_gearset(
+        &self,
+        request: Request<LinkGearsetRequest>,
+    ) -> Result<Response<GearsetReply>, Status> {
+        authenticate(&self.db, &request)
+            .await
+            .auth_access_level_or(AccessLevel::GameServer)?;
+        let args = request.into_inner();
+
+        let changes = link_gearset_query(
+            &self.db,
+            &self.sf,
+            args.character_id,
+            args.gearset_id,
+            args.linked_class_id,
+            args.linked_outfit_id,
+        )
+        .await
+        .map_err(Status::from)?;
+        Ok(Response::new(changes.into()))
+    }
+    async fn get_gearsets(
+        &self,
+        request: Request<GetGearsetsRequest>,
+    ) -> Result<Response<GetGearsetsReply>, Status> {
+        authenticate(&self.db, &request)
+            .await
+            .auth_access_level_or(AccessLevel::GameServer)?;
+        let args = request.into_inner();
+
+        let (gearsets, status, equipped) =
+            get_gearsets_query(&self.db, &self.sf, args.character_id)
+                .await
+                .map_err(Status::from)?;
+        Ok(Response::new(GetGearsetsReply {
+            gearset_list: gearsets.into_iter().map(|g| g.into()).collect(),
+            base_gearset_id: status.base_gearset_id,
+            active_gearset_id: status.active_gearset_id,
+            equipped_slot_list: gear_slot_list(equipped),
+        }))
+    }
+    async fn create_outfit(
+        &self,
+        request: Request<CreateOutfitRequest>,
+    ) -> Result<Response<OutfitReply>, Status> {
+        authenticate(&self.db, &request)
+            .await
+            .auth_access_level_or(AccessLevel::GameServer)?;
+        let args = request.into_inner();
+
+        let changes = create_outfit_query(&self.db, &self.sf, args.character_id, args.name)
+            .await
+            .map_err(Status::from)?;
+        Ok(Response::new(changes.into()))
+    }
+    async fn rename_outfit(
+        &self,
+        request: Request<RenameOutfitRequest>,
+    ) -> Result<Response<OutfitReply>, Status> {
+        authenticate(&self.db, &request)
+            .await
+            .auth_access_level_or(AccessLevel::GameServer)?;
+        let args = request.into_inner();
+
+        let changes = rename_outfit_query(
+            &self.db,
+            &self.sf,
+            args.character_id,
+            args.outfit_id,
+            args.name,
+        )
+        .await
+        .map_err(Status::from)?;
+        Ok(Response::new(changes.into()))
+    }
+    async fn delete_outfit(
+        &self,
+        request: Request<DeleteOutfitRequest>,
+    ) -> Result<Response<OutfitReply>, Status> {
+        authenticate(&self.db, &request)
+            .await
+            .auth_access_level_or(AccessLevel::GameServer)?;
+        let args = request.into_inner();
+
+        let changes = delete_outfit_query(&self.db, &self.sf, args.character_id, args.outfit_id)
+            .await
+            .map_err(Status::from)?;
+        Ok(Response::new(changes.into()))
+    }
+    async fn apply_outfit(
+        &self,
+        request: Request<ApplyOutfitRequest>,
+    ) -> Result<Response<OutfitReply>, Status> {
+        authenticate(&self.db, &request)
+            .await
+            .auth_access_level_or(AccessLevel::GameServer)?;
+        let args = request.into_inner();
+
+        let changes = apply_outfit_query(&self.db, &self.sf, args.character_id, args.outfit_id)
+            .await
+            .map_err(Status::from)?;
+        Ok(Response::new(changes.into()))
+    }
+    async fn apply_glamour(
+        &self,
+        request: Request<ApplyGlamourRequest>,
+    ) -> Result<Response<OutfitReply>, Status> {
+        authenticate(&self.db, &request)
+            .await
+            .auth_access_level_or(AccessLevel::GameServer)?;
+        let args = request.into_inner();
+
+        let changes = apply_glamour_query(
+            &self.db,
+            &self.sf,
+            args.character_id,
+            args.slot,
+            args.content_id,
+            args.outfit_id,
+        )
+        .await
+        .map_err(Status::from)?;
+        Ok(Response::new(changes.into()))
+    }
+    async fn get_outfits(
+        &self,
+        request: Request<GetOutfitsRequest>,
+    ) -> Result<Response<GetOutfitsReply>, Status> {
+        authenticate(&self.db, &request)
+            .await
+            .auth_access_level_or(AccessLevel::GameServer)?;
+        let args = request.into_inner();
+
+        let (outfits, status, appearance) =
+            get_outfits_query(&self.db, &self.sf, args.character_id)
+                .await
+                .map_err(Status::from)?;
+        Ok(Response::new(GetOutfitsReply {
+            outfit_list: outfits.into_iter().map(|o| o.into()).collect(),
+            base_outfit_id: status.base_outfit_id,
+            active_outfit_id: status.active_outfit_id,
+            appearance: Some(appearance.into()),
         }))
     }
+    async fn get_appearance(
+        &self,
+        request: Request<GetAppearanceRequest>,
+    ) -> Result<Response<Appearance>, Status> {
+        authenticate(&self.db, &request)
+            .await
+            .auth_access_level_or(AccessLevel::GameServer)?;
+        let args = request.into_inner();
+
+        let appearance = get_appearance_query(&self.db, &self.sf, args.character_id)
+            .await
+            .map_err(Status::from)?;
+        Ok(Response::new(appearance.into()))
+    }
 }
@@ -1,2 +1,4 @@
+pub mod administration;
+pub mod asset;
 pub mod game_data;
 pub mod users;
@@ -1,15 +1,28 @@
 use crate::{
     api::{
-        users_server::Users, Character, CreateCharacterRequest, LogInCharacterReply,
-        LogInCharacterRequest, LogInReply, LogInRequest, Message, User,
+        Character, CreateCharacterRequest, DeleteUserRequest, LogInCharacterReply,
+        LogInCharacterRequest, LogInReply, LogInRequest, LogOutRequest, Message, RecoverUserReply,
+        RecoverUserRequest, SignUpReply, SignUpRequest, UpdatePasswordRequest,
+        UpdateUsernameRequest, User, VerifyRecoveryCodeReply, VerifyRecoveryCodeRequest,
+        users_server::Users,
     },
     model::fields::Role,
-    queries::{authentication::user_login_query, character::create_character_query},
+    queries::{
+        authentication::{
+            check_user_password_query, delete_user_session_query, delete_user_sessions_query,
+            user_login_query,
+        },
+        character::create_character_query,
+        users::{
+            delete_user_query, recover_user_query, sign_up_query, update_password_query,
+            update_username_query,
+        },
+    },
     utils::{authentication::id_to_base32, transport::authenticate},
 };
 use sonyflake::Sonyflake;
-use sqlx::pool::Pool;
 use sqlx::Sqlite;
+use sqlx::pool::Pool;
 use tonic::{Request, Response, Status};
 
 pub struct UsersService {
@@ -25,10 +38,20 @@ impl UsersService {
 
 #[tonic::async_trait]
 impl Users for UsersService {
-    /// TODO
-    async fn sign_up(&self, request: Request<Message>) -> Result<Response<Message>, Status> {
-        Ok(Response::new(Message {
-            message: format!("Request: {:?}", request),
+    async fn sign_up(
+        &self,
+        request: Request<SignUpRequest>,
+    ) -> Result<Response<SignUpReply>, Status> {
+        let args = request.into_inner();
+
+        let (session_token, recovery_code, session_user) =
+            sign_up_query(&self.db, &self.sf, args.username, &args.password)
+                .await
+                .map_err(Status::invalid_argument)?;
+        Ok(Response::new(SignUpReply {
+            session_token,
+            recovery_code,
+            session_user: Some(User::try_from(session_user)?),
         }))
     }
 
@@ -41,59 +64,119 @@ impl Users for UsersService {
                 .map_err(|_e| Status::unauthenticated("Invalid username or password."))?;
         Ok(Response::new(LogInReply {
             session_token,
-            session_user: Some(User {
-                id: session_user.id,
-                handle: id_to_base32(session_user.handle)
-                    .ok_or(Status::internal("Failed to encode handle."))?,
-                updated_at: session_user.updated_at.and_utc().timestamp(),
-                username: session_user.username,
-                role: session_user.role as i32,
-            }),
+            session_user: Some(User::try_from(session_user)?),
         }))
     }
-    /// TODO
-    async fn log_out(&self, request: Request<Message>) -> Result<Response<Message>, Status> {
-        Ok(Response::new(Message {
-            message: format!("Request: {:?}", request),
-        }))
+
+    async fn log_out(&self, request: Request<LogOutRequest>) -> Result<Response<Message>, Status> {
+        let (user, session) = authenticate(&self.db, &request).await.auth_session_or()?;
+        let args = request.into_inner();
+
+        if args.all_sessions.unwrap_or(false) {
+            let count = delete_user_sessions_query(&self.db, user.id, None)
+                .await
+                .map_err(Status::internal)?;
+            Ok(Response::new(Message {
+                message: format!("Logged out of {} sessions.", count),
+            }))
+        } else {
+            delete_user_session_query(&self.db, &session.id)
+                .await
+                .map_err(Status::internal)?;
+            Ok(Response::new(Message {
+                message: "Logged out.".to_owned(),
+            }))
+        }
     }
-    /// TODO
-    async fn delete_user(&self, request: Request<Message>) -> Result<Response<Message>, Status> {
+
+    async fn delete_user(
+        &self,
+        request: Request<DeleteUserRequest>,
+    ) -> Result<Response<Message>, Status> {
+        let (user, _) = authenticate(&self.db, &request).await.auth_session_or()?;
+        let args = request.into_inner();
+
+        check_user_password_query(&self.db, user.id, &args.password)
+            .await
+            .map_err(|_e| Status::unauthenticated("Invalid password."))?;
+        delete_user_query(&self.db, user.id)
+            .await
+            .map_err(Status::internal)?;
         Ok(Response::new(Message {
-            message: format!("Request: {:?}", request),
+            message: format!("Deleted user '{}'.", user.username),
         }))
     }
-    /// TODO
+
     async fn update_username(
         &self,
-        request: Request<Message>,
-    ) -> Result<Response<Message>, Status> {
-        Ok(Response::new(Message {
-            message: format!("Request: {:?}", request),
-        }))
+        request: Request<UpdateUsernameRequest>,
+    ) -> Result<Response<User>, Status> {
+        let (user, _) = authenticate(&self.db, &request).await.auth_session_or()?;
+        let args = request.into_inner();
+
+        let updated = update_username_query(&self.db, user.id, args.username)
+            .await
+            .map_err(Status::invalid_argument)?;
+        Ok(Response::new(User::try_from(updated)?))
     }
-    /// TODO
+
     async fn update_password(
         &self,
-        request: Request<Message>,
+        request: Request<UpdatePasswordRequest>,
     ) -> Result<Response<Message>, Status> {
+        let (user, session) = authenticate(&self.db, &request).await.auth_session_or()?;
+        let args = request.into_inner();
+
+        let keep_session_id = if args.log_out_other_sessions.unwrap_or(false) {
+            Some(session.id.as_str())
+        } else {
+            None
+        };
+        update_password_query(
+            &self.db,
+            &self.sf,
+            user.id,
+            &args.password,
+            &args.new_password,
+            keep_session_id,
+        )
+        .await
+        .map_err(Status::invalid_argument)?;
         Ok(Response::new(Message {
-            message: format!("Request: {:?}", request),
+            message: "Password updated.".to_owned(),
         }))
     }
-    /// TODO
     async fn verify_recovery_code(
         &self,
-        request: Request<Message>,
-    ) -> Result<Response<Message>, Status> {
-        Ok(Response::new(Message {
-            message: format!("Request: {:?}", request),
+        request: Request<VerifyRecoveryCodeRequest>,
+    ) -> Result<Response<VerifyRecoveryCodeReply>, Status> {
+        let (user, recovery_code) = authenticate(&self.db, &request)
+            .await
+            .auth_recovery_code_or()?;
+
+        Ok(Response::new(VerifyRecoveryCodeReply {
+            user: Some(User::try_from(user)?),
+            is_temporary: recovery_code.is_temporary,
         }))
     }
-    /// TODO
-    async fn recover_user(&self, request: Request<Message>) -> Result<Response<Message>, Status> {
-        Ok(Response::new(Message {
-            message: format!("Request: {:?}", request),
+
+    async fn recover_user(
+        &self,
+        request: Request<RecoverUserRequest>,
+    ) -> Result<Response<RecoverUserReply>, Status> {
+        let (user, recovery_code) = authenticate(&self.db, &request)
+            .await
+            .auth_recovery_code_or()?;
+        let args = request.into_inner();
+
+        let (session_token, new_recovery_code) =
+            recover_user_query(&self.db, &self.sf, &recovery_code, &args.new_password)
+                .await
+                .map_err(Status::invalid_argument)?;
+        Ok(Response::new(RecoverUserReply {
+            session_token,
+            recovery_code: new_recovery_code,
+            session_user: Some(User::try_from(user)?),
         }))
     }
 
@@ -27,6 +27,17 @@ pub fn hash_password(password: &str) -> Option<String> {
     )
 }
 
+pub fn validate_password(password: &str) -> Result<(), String> {
+    let length = password.chars().count();
+    if length < 8 {
+        return Err("Password must be at least 8 characters.".to_owned());
+    }
+    if length > 128 {
+        return Err("Password must be at most 128 characters.".to_owned());
+    }
+    Ok(())
+}
+
 pub fn verify_password(password: &str, stored_hash: &str) -> bool {
     if let Ok(hash) = PasswordHash::try_from(stored_hash) {
         Argon2::default()
@@ -135,14 +146,14 @@ pub async fn authenticate_from_token(db: &Pool<Sqlite>, token: Option<&str>) ->
                     Ok((user, session)) => {
                         AuthStatus::Authenticated(AuthType::UserSession(user, session))
                     }
-                    Err(_) => AuthStatus::Unauthenticated,
+                    Err(e) => e.into(),
                 }
             } else if token.contains('_') {
                 match validate_access_token_query(db, token).await {
                     Ok(access_token) => {
                         AuthStatus::Authenticated(AuthType::AccessToken(access_token))
                     }
-                    Err(_) => AuthStatus::Unauthenticated,
+                    Err(e) => e.into(),
                 }
             } else {
                 match validate_recovery_code_query(db, token).await {
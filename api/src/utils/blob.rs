@@ -0,0 +1,68 @@
+use libsqlite3_sys::{
+    SQLITE_OK, sqlite3, sqlite3_blob, sqlite3_blob_bytes, sqlite3_blob_close, sqlite3_blob_open,
+    sqlite3_blob_read, sqlite3_errmsg,
+};
+use std::{
+    ffi::{CStr, CString, c_int},
+    ptr::{self, NonNull},
+};
+
+/// Reads up to `length` bytes of a BLOB or TEXT value starting at `offset` using SQLite's incremental blob I/O, so the rest of the value is never loaded into memory.
+/// Returns the bytes read along with the total size of the value in bytes.
+pub fn read_blob(
+    handle: NonNull<sqlite3>,
+    table: &str,
+    column: &str,
+    row_id: i64,
+    offset: i64,
+    length: usize,
+) -> Result<(Vec<u8>, i64), String> {
+    let table = CString::new(table).map_err(|e| e.to_string())?;
+    let column = CString::new(column).map_err(|e| e.to_string())?;
+    let mut blob: *mut sqlite3_blob = ptr::null_mut();
+
+    // SAFETY: `handle` is a locked connection handle and every pointer passed in outlives the call. The blob handle is always closed before returning.
+    unsafe {
+        let rc = sqlite3_blob_open(
+            handle.as_ptr(),
+            c"main".as_ptr(),
+            table.as_ptr(),
+            column.as_ptr(),
+            row_id,
+            0,
+            &mut blob,
+        );
+        if rc != SQLITE_OK {
+            sqlite3_blob_close(blob);
+            return Err(error_message(handle));
+        }
+        let size = sqlite3_blob_bytes(blob) as i64;
+        if offset > size {
+            sqlite3_blob_close(blob);
+            return Err("Offset is past the end of the data.".to_owned());
+        }
+        let mut data = vec![0u8; (size - offset).min(length as i64) as usize];
+        if !data.is_empty() {
+            let rc = sqlite3_blob_read(
+                blob,
+                data.as_mut_ptr().cast(),
+                data.len() as c_int,
+                offset as c_int,
+            );
+            if rc != SQLITE_OK {
+                sqlite3_blob_close(blob);
+                return Err(error_message(handle));
+            }
+        }
+        sqlite3_blob_close(blob);
+        Ok((data, size))
+    }
+}
+
+/// # Safety
+/// `handle` must be a valid, locked connection handle.
+unsafe fn error_message(handle: NonNull<sqlite3>) -> String {
+    unsafe { CStr::from_ptr(sqlite3_errmsg(handle.as_ptr())) }
+        .to_string_lossy()
+        .into_owned()
+}
@@ -1,9 +1,11 @@
 pub mod authentication;
+pub mod blob;
 pub mod transport;
 use crate::model::fields::AssetData;
 use chrono::{DateTime, NaiveDateTime, Utc};
 use num::{FromPrimitive, Integer, ToPrimitive};
 use rand::distr::{Alphanumeric, SampleString};
+use sha2::{Digest, Sha256};
 use sonyflake::{decompose, Builder, Sonyflake};
 use std::{
     fs::{self, DirEntry, OpenOptions},
@@ -53,17 +55,17 @@ pub fn validate_and_format_name(name: String) -> Option<String> {
     let formatted = name.trim();
     let length = formatted.len();
 
-    if length < 2 || length > 30 || bad_char_regex.is_match(formatted) {
+    if !(2..=30).contains(&length) || bad_char_regex.is_match(formatted) {
         return None;
     }
     let mut space_count = 0;
     let mut last_char_is_space = false;
     for c in formatted.chars() {
         if c == ' ' {
-            if space_count == 3 || last_char_is_space == true {
+            if space_count == 3 || last_char_is_space {
                 return None;
             }
-            space_count = space_count + 1;
+            space_count += 1;
             last_char_is_space = true;
         } else {
             last_char_is_space = false;
@@ -72,6 +74,74 @@ pub fn validate_and_format_name(name: String) -> Option<String> {
     Some(formatted.to_owned())
 }
 
+/// Usernames follow the same rules as other names but must be at most 20 characters and contain no whitespace.
+pub fn validate_and_format_username(username: String) -> Option<String> {
+    let formatted = validate_and_format_name(username)?;
+    if formatted.len() > 20 || formatted.contains(' ') {
+        return None;
+    }
+    Some(formatted)
+}
+
+/// String IDs such as `GameServer.id` and `World.id` are lowercased and must be 2 to 32 ASCII letters, digits, or underscores.
+pub fn validate_and_format_string_id(id: &str) -> Option<String> {
+    let id_regex = regex!("^[a-z0-9_]{2,32}$");
+    let formatted = id.trim().to_lowercase();
+    if !id_regex.is_match(&formatted) {
+        return None;
+    }
+    Some(formatted)
+}
+
+/// Region codes must be case sensitive tz database identifiers. Ex: 'US/Eastern'
+pub fn validate_region_code(region_code: &str) -> bool {
+    region_code.parse::<chrono_tz::Tz>().is_ok()
+}
+
+/// Virtual path prefix user generated assets are saved under, followed by the uploader's base32 handle. Data files can't use it.
+pub const USER_ASSET_PATH_PREFIX: &str = "/user/";
+
+pub fn is_user_asset_path(path: &str) -> bool {
+    path.trim()
+        .trim_start_matches('/')
+        .to_lowercase()
+        .starts_with(USER_ASSET_PATH_PREFIX.trim_start_matches('/'))
+}
+
+/// User generated asset paths are relative to the uploader's prefix and must be 1 to 8 `/` separated names of at most 64 ASCII letters, digits, `.`, `_` or `-`, not starting with `.`.
+pub fn validate_and_format_user_asset_path(path: &str) -> Option<String> {
+    let name_regex = regex!("^[A-Za-z0-9_-][A-Za-z0-9._-]{0,63}$");
+    let formatted = path.trim().trim_matches('/');
+    let names: Vec<&str> = formatted.split('/').collect();
+    if names.len() > 8 || !names.iter().all(|name| name_regex.is_match(name)) {
+        return None;
+    }
+    Some(formatted.to_owned())
+}
+
+/// `Content` id of the BaseClass, the class every character has without a `ClassItem`. Its row is created by a migration.
+pub const BASE_CLASS_CONTENT_ID: i64 = 0;
+/// Name of the gearset and outfit every character has, which equipping gear and applying glamours modify directly.
+pub const BASE_SET_NAME: &str = "BASE";
+/// Ids an `EquipmentSlot` can have, one for each item column of `Gearset` and `Outfit`.
+pub const EQUIPMENT_SLOT_IDS: Range<i64> = 0..16;
+/// Most new item instances a single `add_item` can create, since each one is its own row.
+pub const MAX_ADDED_INSTANCES: i64 = 1000;
+
+pub const DEFAULT_PAGE_SIZE: i32 = 20;
+pub const MAX_PAGE_SIZE: i32 = 100;
+
+/// Turns optional zero indexed paging arguments into a `(page, page_size, offset)` tuple, clamping `page_size` to `MAX_PAGE_SIZE`.
+pub fn parse_paging(page_size: Option<i32>, page: Option<i32>) -> (i32, i32, i64) {
+    let page_size = page_size.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let page = page.unwrap_or(0).max(0);
+    (page, page_size, page as i64 * page_size as i64)
+}
+
+pub fn page_count(total: i64, page_size: i32) -> i32 {
+    ((total + page_size as i64 - 1) / page_size as i64) as i32
+}
+
 pub fn parse_range(range: String) -> Option<Range<u16>> {
     let mut split = range.splitn(2, "..");
     let first = split.next()?.parse::<u16>().ok()?;
@@ -118,7 +188,7 @@ pub fn append_secret_to_file(new_line: String) {
         Ok(f) => f,
         Err(e) => {
             eprintln!("Couldn't write to file: {}. {}", new_line, e);
-            return ();
+            return;
         }
     };
     if let Err(e) = writeln!(file, "{}", new_line) {
@@ -132,12 +202,31 @@ pub fn get_magic_cookie() -> magic::Cookie<magic::cookie::Load> {
     cookie.load(&database).unwrap()
 }
 
+/// Gets the lowercase hex SHA-256 hash stored in `Asset.hash`.
+pub fn hash_asset_data(data: &[u8]) -> String {
+    let hash = Sha256::digest(data);
+    let mut buf = [0u8; 64];
+    base16ct::lower::encode_str(&hash, &mut buf)
+        .expect("SHA-256 hex digest is 64 bytes")
+        .to_owned()
+}
+
+/// Returns the file as `AssetData` along with its size, MIME type and hash.
 pub fn read_asset_file(
     path: &str,
     magic_cookie: &magic::Cookie<magic::cookie::Load>,
-) -> Result<(AssetData, i64, String), io::Error> {
+) -> Result<(AssetData, i64, String, String), io::Error> {
     let path = Path::new(path);
     let data: Vec<u8> = fs::read(path)?;
+    read_asset_data(data, magic_cookie)
+}
+
+/// Returns `data` as `AssetData` along with its size, MIME type and hash, with text and JSON stored as a string.
+pub fn read_asset_data(
+    data: Vec<u8>,
+    magic_cookie: &magic::Cookie<magic::cookie::Load>,
+) -> Result<(AssetData, i64, String, String), io::Error> {
+    let hash = hash_asset_data(&data);
     let file_type = magic_cookie
         .buffer(&data)
         .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
@@ -153,8 +242,9 @@ pub fn read_asset_file(
             ),
             size,
             file_type,
+            hash,
         ))
     } else {
-        Ok((AssetData::Blob(data), size, file_type))
+        Ok((AssetData::Blob(data), size, file_type, hash))
     }
 }
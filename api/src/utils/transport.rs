@@ -7,10 +7,9 @@ pub async fn authenticate<T>(db: &Pool<Sqlite>, req: &Request<T>) -> AuthStatus
     let authorization = req
         .metadata()
         .get("authorization")
-        .map(|m| m.to_str().ok())
-        .flatten();
+        .and_then(|m| m.to_str().ok());
     println!("Token: {:?}\n", authorization);
-    let auth_status = authenticate_from_token(&db, authorization).await;
+    let auth_status = authenticate_from_token(db, authorization).await;
     println!("Auth Status: {:?}\n", auth_status);
     auth_status
 }
@@ -0,0 +1,107 @@
+use crate::{
+    model::ContentChange,
+    queries::data_import::data_import,
+    utils::{current_timestamp, read_dir_recursive},
+};
+use sqlx::{Pool, Sqlite};
+use std::{
+    collections::BTreeMap,
+    time::{Duration, SystemTime},
+};
+use tokio::{runtime::Handle, sync::broadcast, task::spawn_blocking};
+
+/// How often the watched directories are scanned for changes.
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+/// Directories `data_import` reads data files and asset source files from.
+const WATCHED_DIRS: [&str; 2] = ["../data/content", "../data/assets"];
+const DATA_DIR: &str = "../data/";
+
+/// Modified time and size of every file under the watched directories, keyed by path relative to the data directory.
+type Snapshot = BTreeMap<String, (Option<SystemTime>, u64)>;
+
+/// Re-runs `data_import` whenever a file under the watched directories is added, changed or removed, then sends a `ContentChange` once the import is committed.
+/// A change is only imported after the directories stay the same for a whole interval, so files still being saved aren't picked up.
+/// Rows are never pruned, run `script migrate --prune` for that.
+/// Scanning and importing run on blocking threads so their file reads, libmagic and hashing don't stall the servers.
+pub async fn watch_content(db: Pool<Sqlite>, changes: broadcast::Sender<ContentChange>) {
+    let mut interval = tokio::time::interval(WATCH_INTERVAL);
+    let mut imported = scan().await;
+    let mut pending: Option<Snapshot> = None;
+    println!("  Watching {} for changes.\n", WATCHED_DIRS.join(" and "));
+    loop {
+        interval.tick().await;
+        let current = scan().await;
+        if current == imported {
+            pending = None;
+            continue;
+        }
+        if pending.as_ref() != Some(&current) {
+            pending = Some(current);
+            continue;
+        }
+        pending = None;
+
+        let changed_files = changed_files(&imported, &current);
+        imported = current;
+        println!("  Data files changed: {}", changed_files.join(", "));
+        // The import holds a libmagic cookie across awaits, which can't be sent between threads, so it's driven to completion on a blocking thread.
+        let import_db = db.clone();
+        let handle = Handle::current();
+        let imported_version =
+            spawn_blocking(move || handle.block_on(data_import(&import_db, None, false)))
+                .await
+                .map_err(|e| e.to_string())
+                .and_then(|result| result);
+        match imported_version {
+            Ok(version) => {
+                println!(
+                    "  Reloaded content for game version: '{}'.\n",
+                    version.game_version
+                );
+                // Sending only fails when nobody is watching.
+                let _ = changes.send(ContentChange {
+                    changed_at: current_timestamp(),
+                    game_version: version.game_version,
+                    changed_files,
+                });
+            }
+            Err(e) => eprintln!("  Failed to import changed data files: {}\n", e),
+        }
+    }
+}
+
+async fn scan() -> Snapshot {
+    spawn_blocking(snapshot).await.unwrap_or_default()
+}
+
+fn snapshot() -> Snapshot {
+    let mut snapshot = Snapshot::new();
+    for dir in WATCHED_DIRS {
+        let Ok(entries) = read_dir_recursive(dir) else {
+            continue;
+        };
+        for entry in entries {
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            let path = entry.path().to_string_lossy().into_owned();
+            snapshot.insert(
+                path.trim_start_matches(DATA_DIR).to_owned(),
+                (metadata.modified().ok(), metadata.len()),
+            );
+        }
+    }
+    snapshot
+}
+
+/// Paths added, changed or removed between two snapshots.
+fn changed_files(old: &Snapshot, new: &Snapshot) -> Vec<String> {
+    let mut changed: Vec<String> = new
+        .iter()
+        .filter(|(path, file)| old.get(*path) != Some(file))
+        .map(|(path, _)| path.to_owned())
+        .collect();
+    changed.extend(old.keys().filter(|path| !new.contains_key(*path)).cloned());
+    changed.sort();
+    changed
+}
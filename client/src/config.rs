@@ -2,5 +2,5 @@
 pub const CLIENT_GAME_ID: &str = "project_comet";
 pub const CLIENT_GAME_VERSION: &str = "0.1.0";
 pub const DEFAULT_API_ADDRESS: &str = "127.0.0.1:50051";
-pub const DEFAULT_ASSET_ADDRESS: &str = "127.0.0.1/asset";
+pub const DEFAULT_ASSET_ADDRESS: &str = "127.0.0.1:8000/asset";
 pub const DEFAULT_FONT: &str = "fira_mono_medium.ttf";